@@ -0,0 +1,142 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, ToTokens};
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, Member, Type};
+
+// Every field of the struct is a snapshot of the component of the same type, so no two fields can have the same type.
+// A plain field is saved from the component and restored into it.
+// #[rollback(save_only)]       the field is saved but not restored
+// #[rollback(derived = path)]  the component is not saved, on restore it is recomputed by `path(&self)`, the field is set to Default on save
+
+enum Mode {
+    Both,
+    SaveOnly,
+    Derived(Expr),
+}
+
+struct Field {
+    member: Member,
+    ty: Type,
+    mode: Mode,
+}
+
+/// Implements `RollbackCapable` for a struct whose fields are snapshots of components, see the comment at the top of this file.
+/// The struct must not be both `Component` and `Clone`, those already have the blanket implementation.
+#[proc_macro_derive(RollbackCapable, attributes(rollback))]
+pub fn derive_rollback_capable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match derive(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn derive(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "RollbackCapable can not be derived for generic structs"));
+    }
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "RollbackCapable can only be derived for structs"));
+    };
+
+    let fields = match &data.fields {
+        Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+        Fields::Unnamed(fields) => fields.unnamed.iter().collect::<Vec<_>>(),
+        Fields::Unit => Vec::new(),
+    };
+    let fields = fields.into_iter().enumerate().map(|(i, field)| {
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(i.into()),
+        };
+        let mut mode = Mode::Both;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("rollback")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("save_only") {
+                    mode = Mode::SaveOnly;
+                }else if meta.path.is_ident("derived") {
+                    mode = Mode::Derived(meta.value()?.parse()?);
+                }else{
+                    return Err(meta.error("expected `save_only` or `derived = path`"))
+                }
+                Ok(())
+            })?;
+        }
+        Ok(Field { member, ty: field.ty.clone(), mode })
+    }).collect::<syn::Result<Vec<_>>>()?;
+
+    //the queries would access the same component twice
+    let type_names = fields.iter().map(|x| x.ty.to_token_stream().to_string()).collect::<Vec<_>>();
+    for (i, field) in fields.iter().enumerate() {
+        if type_names[..i].contains(&type_names[i]) {
+            return Err(syn::Error::new_spanned(&field.ty, format!("the type `{}` is already used by another field, every field has to have a different type", type_names[i])));
+        }
+    }
+
+    let restored = fields.iter().filter(|x| !matches!(x.mode, Mode::SaveOnly)).collect::<Vec<_>>();
+    let saved = fields.iter().filter(|x| matches!(x.mode, Mode::Both | Mode::SaveOnly)).collect::<Vec<_>>();
+
+    let restore_types = restored.iter().map(|x| &x.ty).collect::<Vec<_>>();
+    let restore_vars = (0..restored.len()).map(|i| format_ident!("__restore{i}")).collect::<Vec<_>>();
+    let restore_values = restored.iter().map(|x| {
+        let member = &x.member;
+        match &x.mode {
+            Mode::Derived(path) => quote! { #path(self) },
+            _ => quote! { ::core::clone::Clone::clone(&self.#member) },
+        }
+    }).collect::<Vec<_>>();
+
+    //derived fields only name the component type, they are read here so that they are not reported as dead code
+    let derived_members = fields.iter().filter(|x| matches!(x.mode, Mode::Derived(_))).map(|x| &x.member).collect::<Vec<_>>();
+
+    let save_types = saved.iter().map(|x| &x.ty).collect::<Vec<_>>();
+    let save_vars = (0..saved.len()).map(|i| format_ident!("__save{i}")).collect::<Vec<_>>();
+    let save_fields = fields.iter().map(|x| {
+        let member = &x.member;
+        match saved.iter().position(|y| y.member == x.member) {
+            Some(i) => {
+                let var = &save_vars[i];
+                quote! { #member: ::core::clone::Clone::clone(#var) }
+            },
+            None => quote! { #member: ::core::default::Default::default() },
+        }
+    }).collect::<Vec<_>>();
+
+    Ok(quote! {
+        impl ::bevy_gravirollback::systems::RollbackCapable for #name {
+            type RestoreQuery<'a> = (#(&'a mut #restore_types,)*);
+            type RestoreExtraParam<'a> = ();
+            type SaveQuery<'a> = (#(&'a #save_types,)*);
+            type SaveExtraParam<'a> = ();
+
+            #[allow(unused_mut, unused_variables)]
+            fn restore(
+                &self,
+                q: <Self::RestoreQuery<'_> as ::bevy::ecs::query::WorldQuery>::Item<'_>,
+                _extra: &mut ::bevy::ecs::system::StaticSystemParam<Self::RestoreExtraParam<'_>>,
+            ) {
+                let (#(mut #restore_vars,)*) = q;
+                #(*#restore_vars = #restore_values;)*
+                #(let _ = &self.#derived_members;)*
+            }
+
+            #[allow(unused_variables)]
+            fn save(
+                q: <Self::SaveQuery<'_> as ::bevy::ecs::query::WorldQuery>::Item<'_>,
+                _extra: &mut ::bevy::ecs::system::StaticSystemParam<Self::SaveExtraParam<'_>>,
+            ) -> Self {
+                let (#(#save_vars,)*) = q;
+                Self { #(#save_fields,)* }
+            }
+
+            fn insert(&self, entity: ::bevy::ecs::entity::Entity, commands: &mut ::bevy::ecs::system::Commands) {
+                commands.entity(entity).insert((#(#restore_values,)*));
+            }
+
+            fn remove(entity: ::bevy::ecs::entity::Entity, commands: &mut ::bevy::ecs::system::Commands) {
+                commands.entity(entity).remove::<(#(#restore_types,)*)>();
+            }
+        }
+    })
+}
@@ -35,6 +35,33 @@ impl RollbackCapable for Parameter {
     }
 }
 
+/// With the `derive` feature the implementation can be generated for a struct whose fields are snapshots of components.
+/// `Velocity` is only saved, it is not restored. `Speed` is not saved at all and it is recomputed from the saved `Velocity` on restore.
+#[cfg(feature = "derive")]
+#[derive(RollbackCapable, Default)]
+struct Body {
+    transform: Transform,
+    #[rollback(save_only)]
+    velocity: Velocity,
+    #[rollback(derived = Body::speed)]
+    speed: Speed,
+}
+
+#[cfg(feature = "derive")]
+impl Body {
+    fn speed(&self) -> Speed {
+        Speed(self.velocity.0.length())
+    }
+}
+
+#[cfg(feature = "derive")]
+#[derive(Component, Clone, Default)]
+struct Velocity(Vec3);
+
+#[cfg(feature = "derive")]
+#[derive(Component, Clone, Default)]
+struct Speed(f32);
+
 /// This can also be used for initializing/deinitializing rollback entities
 /// -> probably not a good idea?
 #[derive(Component)]
@@ -44,4 +71,24 @@ struct PlayerMarker;
 //    type RestoreQuery<'a> = ();
 //}
 
-fn main() {}
\ No newline at end of file
+fn main() {
+    #[cfg(feature = "derive")]
+    {
+        use bevy_gravirollback::prelude::*;
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugins((RollbackPlugin::<8>, RollbackSchedulePlugin::<8>::default()));
+        RollbackSystemConfigurator::<8>::default().add::<(Body,)>().apply(&mut app);
+        let entity = app.world_mut().spawn((RollbackID(1), Transform::default(), Velocity(Vec3::new(3.0, 4.0, 0.0)), Speed(0.0), Rollback::<Body, 8>::default())).id();
+        for frame in 1..=3 {
+            app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+            app.update();
+        }
+
+        //the rollback restores frame 1 and recomputes `Speed` from the saved `Velocity`
+        app.world_mut().resource_mut::<Rollback<Modified, 8>>().set(1, Modified(true));
+        app.update();
+        println!("speed after the rollback: {}", app.world().get::<Speed>(entity).unwrap().0);
+    }
+}
\ No newline at end of file
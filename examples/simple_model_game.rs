@@ -35,7 +35,7 @@ const LEN: usize = 128;
 type Rollback<T> = bevy_gravirollback::Rollback<T, LEN>;
 
 //copied from bevy_gravirollback::for_user and modified to use our LEN
-fn make_rollback<T: Component + Default>(component: T) -> (T, Rollback<T>) {
+fn make_rollback<T: Component>(component: T) -> (T, Rollback<T>) {
     (component, Rollback::default())
 }
 
@@ -48,6 +48,12 @@ fn main() {
         RollbackPlugin::<LEN>,
         RollbackSchedulePlugin::<LEN>::default(),
         ExistencePlugin::<LEN>,
+        RollbackIdPlugin::<LEN>,
+        RollbackInputPlugin::<BallInput, LEN> {
+            players: 1,
+            predict: |_| BallInput::default(),  //the jump signal should not be repeated
+            ..default()
+        },
     ))
 
     //TODO: these should be probably automaticaly registered
@@ -63,8 +69,6 @@ fn main() {
     .register_type::<Exists>()
     .register_type::<Rollback<Exists>>()
 
-    .register_type::<Rollback<Transform>>()
-
     .insert_resource(AmbientLight {
         color: Color::srgb(1.0,1.0,1.0),
         brightness: 0.2,
@@ -79,20 +83,14 @@ fn main() {
         get_input,
     ).in_set(RollbackProcessSet::HandleIO));
 
-    RollbackSystemConfigurator::<LEN>::default()
-        .add::<(
-            Transform,  //even when having just one type, you have to wrap it into a tuple like this: (T,)
-            //Mesh3d,   //you can register multiple at the same time
-            //GlobalTransform,
-            //etc...
-        )>()
+    //registers the rollback systems and the reflection of Rollback<Transform>
+    RollbackConfig::<LEN>::new()
+        .register_component::<Transform>()
+        //.register_component::<GlobalTransform>()  //you can register multiple types, each with its own custom systems
+        //    .set_restore(...)
         .apply(&mut app);
     
     app
-    .add_systems(RollbackSave,clear_resource_input_option::<PlayerInput,LEN>)
-    .add_systems(RollbackUpdate, restore_resource_option::<PlayerInput,LEN>.in_set(RollbackUpdateSet::LoadInputs))
-    .insert_resource(Rollback::<Option<PlayerInput>>::default())
-    
     .add_systems(RollbackUpdate,(
         (
             jump,
@@ -107,8 +105,10 @@ fn main() {
     app.run();
 }
 
-#[derive(Resource, Clone, Default)]  //TODO: remove Default requirement
-struct PlayerInput;
+#[derive(Clone, Default, PartialEq)]
+struct BallInput {
+    jump: bool,
+}
 
 #[derive(Component)]
 struct BallMarker;
@@ -116,6 +116,7 @@ struct BallMarker;
 fn setup(
     mut commands: Commands,
     mut window: Query<&mut Window, With<PrimaryWindow>>,
+    mut ids: ResMut<RollbackIdAllocator>,
 ) {
     window.single_mut().present_mode = PresentMode::AutoNoVsync;
 
@@ -124,7 +125,7 @@ fn setup(
         Transform::from_xyz(0.0, 0.0, 30.0)
     ));
     println!("running setup");
-    let id = RollbackID(0);     //I should make sure its unique
+    let id = ids.allocate();
     //commands.add(spawn(spawn_ball, (Transform::from_xyz(0.0, 10.0, 0.0), id)));
     //commands.add(spawn2(|world| spawn_ball2(Transform::from_xyz(0.0, 10.0, 0.0), id, world)));
     commands.queue(spawn3(spawn_ball3(Transform::from_xyz(0.0, 10.0, 0.0), id)));
@@ -224,8 +225,8 @@ fn ball_existence(mut q: Query<(&Exists, &mut Visibility), (With<BallMarker>, Ch
     }
 }
 
-fn jump(mut q: Query<&mut Transform, With<BallMarker>>, input: Option<Res<PlayerInput>>) {
-    if input.is_some() {
+fn jump(mut q: Query<&mut Transform, With<BallMarker>>, inputs: Res<FrameInputs<BallInput>>) {
+    if inputs.get(PlayerHandle(0)).is_some_and(|x| x.input.jump) {
         let Ok(mut transform) = q.get_single_mut() else {return};
         transform.translation.y = 10.0;
     }
@@ -267,19 +268,9 @@ fn advance_frame(
     }
 }
 
-//TODO: this should be made by the library
-#[derive(Event)]
-struct NewInputEvent {
-    frame: u64,
-    data: (/*****/),
-}
-
 fn get_input(
     mut waiting: ResMut<WaitingInputs>,
-    mut modified: ResMut<Rollback<Modified>>,
-    frames: Res<Rollback<Frame>>,
-    mut inputs: ResMut<Rollback<Option<PlayerInput>>>,
-    //mut events: EventWriter<NewInputEvent>,
+    mut events: EventWriter<InsertInput<BallInput>>,
 ) {
     let max = 15;
     
@@ -290,21 +281,12 @@ fn get_input(
     if flag && count!=0 {
         let frame = waiting.0.remove(0);
         
-        //this should be done automaticaly:
-        let index = index::<LEN>(frame);
-        if frames[index].0 == frame {
-            //insert the input
-            inputs.0[index] = Some(PlayerInput);
-
-            modified[index].0 = true;
-        }else{
-            todo!("dropped snapshot");
-        }
-
-        //this should be used instead:
-        //events.send(NewInputEvent {
-        //    frame,
-        //    data: (),
-        //});
+        //the frame is marked as Modified automatically if the input differs from the prediction,
+        //inputs which are too old are reported by RollbackEventError
+        events.send(InsertInput {
+            frame,
+            player: PlayerHandle(0),
+            data: BallInput { jump: true },
+        });
     }
 }
\ No newline at end of file
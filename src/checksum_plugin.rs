@@ -0,0 +1,316 @@
+use std::hash::{Hash, Hasher};
+
+use bevy::prelude::*;
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::ecs::schedule::SystemConfigs;
+use bevy::reflect::ReflectRef;
+
+use crate::*;
+
+/// Enables the [`RollbackChecksum`] [`Schedule`] which computes a [`Checksum`] of every saved frame
+/// and stores it in [`Rollback<Checksum, LEN>`]. The checksums are computed by [`ChecksumHasher`], so they can be compared between platforms.
+/// Types are added to the checksum through [`RollbackSystemConfigurator::add_checksum`](crate::rollback_config_plugin::RollbackSystemConfigurator::add_checksum)
+/// and [`RollbackSystemConfigurator::add_resource_checksum`](crate::rollback_config_plugin::RollbackSystemConfigurator::add_resource_checksum),
+/// or by [`RollbackTypeConfig::checksum`](crate::rollback_config_plugin::RollbackTypeConfig::checksum) of the [`RollbackConfig`](crate::rollback_config_plugin::RollbackConfig) builder.
+pub struct ChecksumPlugin<const LEN: usize>;
+
+impl<const LEN: usize> Plugin for ChecksumPlugin<LEN> {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Rollback<Checksum, LEN>>()
+        .init_resource::<ChecksumAccumulator>()
+        .init_resource::<ChecksumConverged>()
+        .init_schedule(RollbackChecksum)
+        .configure_sets(RollbackChecksum, (ChecksumSet::Begin, ChecksumSet::Hash, ChecksumSet::Store).chain())
+        .add_systems(RollbackChecksum, (
+            begin_checksum.in_set(ChecksumSet::Begin),
+            store_checksum::<LEN>.in_set(ChecksumSet::Store),
+        ));
+
+        #[cfg(feature = "growable")]
+        app.add_systems(schedule_plugin::RollbackResize, systems::resize_rollback_resource::<Checksum, LEN>);
+    }
+}
+
+/// Compute the [`Checksum`] of the frame that was just saved. The frame is in [`Frame`] [`Resource`].
+/// This [`Schedule`] is run by [`rollback_save_system`](schedule_plugin::rollback_save_system) right after [`RollbackSave`](schedule_plugin::RollbackSave), if it exists.
+#[derive(ScheduleLabel, Hash, Debug, PartialEq, Eq, Clone)]
+pub struct RollbackChecksum;
+
+/// This [`SystemSet`] runs in the [`RollbackChecksum`] [`Schedule`]
+#[derive(SystemSet, Hash, Debug, PartialEq, Eq, Clone)]
+pub enum ChecksumSet {
+    /// Reset the [`ChecksumAccumulator`]
+    Begin,
+    /// Add the checksum of each type into the [`ChecksumAccumulator`]
+    Hash,
+    /// Store the [`ChecksumAccumulator`] into [`Rollback<Checksum>`]
+    Store,
+}
+
+/// Checksum of all the rollback state registered for checksumming in a single frame
+#[derive(Resource, Reflect, Default, Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checksum(pub u64);
+
+/// The checksum of the frame which is currently being computed.
+/// The checksums of the types are combined by wrapping addition so the order in which the systems run does not matter.
+#[derive(Resource, Default, Debug)]
+pub struct ChecksumAccumulator(pub u64);
+
+impl ChecksumAccumulator {
+    pub fn add(&mut self, type_checksum: u64) {
+        self.0 = self.0.wrapping_add(type_checksum);
+    }
+}
+
+/// Whether the [`Checksum`] of the frame which was just saved is the same as the one stored for that frame before,
+/// which means that the resimulation of the frame produced the same state as its previous simulation
+#[derive(Resource, Default, Debug)]
+pub struct ChecksumConverged(pub bool);
+
+/// The checksums of every type and [`RollbackID`] that were added into the [`ChecksumAccumulator`] in the current frame.
+/// It is only recorded when this [`Resource`] exists, which is used by the [`SyncTestPlugin`](crate::sync_test_plugin::SyncTestPlugin).
+#[derive(Resource, Default, Debug)]
+pub struct ChecksumDetails(pub Vec<ChecksumEntry>);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChecksumEntry {
+    pub type_name: &'static str,
+    /// `None` for resources
+    pub id: Option<RollbackID>,
+    pub checksum: u64,
+}
+
+/// The 64 bit FNV-1a hash with its standard offset basis, used for all the checksums.
+/// Integers are hashed as little endian bytes, so the result does not depend on the platform.
+#[derive(Clone, Copy, Debug)]
+pub struct ChecksumHasher(pub u64);
+
+impl Default for ChecksumHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for ChecksumHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64);
+    }
+}
+
+pub fn begin_checksum(
+    mut accumulator: ResMut<ChecksumAccumulator>,
+    details: Option<ResMut<ChecksumDetails>>,
+) {
+    accumulator.0 = 0;
+    if let Some(mut details) = details {
+        details.0.clear();
+    }
+}
+
+pub fn store_checksum<const LEN: usize>(
+    current_frame: Res<Frame>,
+    accumulator: Res<ChecksumAccumulator>,
+    mut checksums: ResMut<Rollback<Checksum, LEN>>,
+    mut converged: ResMut<ChecksumConverged>,
+) {
+    let checksum = Checksum(accumulator.0);
+    converged.0 = checksums.get(current_frame.0) == Some(&checksum);
+    checksums.set(current_frame.0, checksum);
+}
+
+/// Hashes the snapshots of `T` saved for the current [`Frame`] of all entities in [`RollbackID`] order
+pub fn checksum_component<T: PartialReflect + TypePath, const LEN: usize>(
+    current_frame: Res<Frame>,
+    query: Query<(&RollbackID, &Rollback<T, LEN>)>,
+    mut accumulator: ResMut<ChecksumAccumulator>,
+    mut details: Option<ResMut<ChecksumDetails>>,
+) {
+    let type_name = std::any::type_name::<T>();
+    let mut snapshots = query.iter().map(|(id, r)| (*id, hash_snapshot(r.get(current_frame.0)))).collect::<Vec<_>>();
+    snapshots.sort_unstable_by_key(|(id, _)| *id);
+
+    let mut hasher = ChecksumHasher::default();
+    T::type_path().hash(&mut hasher);
+    for (id, checksum) in snapshots {
+        (id, checksum).hash(&mut hasher);
+        if let Some(details) = &mut details {
+            details.0.push(ChecksumEntry { type_name, id: Some(id), checksum });
+        }
+    }
+    accumulator.add(hasher.finish());
+}
+
+/// Hashes the snapshot of the [`Resource`] `T` saved for the current [`Frame`]
+pub fn checksum_resource<T: PartialReflect + TypePath, const LEN: usize>(
+    current_frame: Res<Frame>,
+    rollback: Res<Rollback<T, LEN>>,
+    mut accumulator: ResMut<ChecksumAccumulator>,
+    details: Option<ResMut<ChecksumDetails>>,
+) {
+    let type_name = std::any::type_name::<T>();
+    let checksum = hash_snapshot(rollback.get(current_frame.0));
+
+    let mut hasher = ChecksumHasher::default();
+    (T::type_path(), checksum).hash(&mut hasher);
+    if let Some(mut details) = details {
+        details.0.push(ChecksumEntry { type_name, id: None, checksum });
+    }
+    accumulator.add(hasher.finish());
+}
+
+fn hash_snapshot<T: PartialReflect>(snapshot: Option<&T>) -> u64 {
+    let mut hasher = ChecksumHasher::default();
+    match snapshot {
+        Some(snapshot) => {
+            true.hash(&mut hasher);
+            reflect_checksum(snapshot, &mut hasher);
+        },
+        None => false.hash(&mut hasher),   //the slot was not written for this frame
+    }
+    hasher.finish()
+}
+
+/// Hashes any reflected value by walking its structure. Floats are hashed by their bits, lengths and indices as `u64`.
+/// Map and set entries are combined independently of their iteration order.
+/// Opaque types other than the primitives are hashed by [`PartialReflect::reflect_hash`], if they do not support it only their type is hashed.
+/// The hasher of [`PartialReflect::reflect_hash`] is not specified, so such types can make the checksum differ between platforms.
+pub fn reflect_checksum(value: &dyn PartialReflect, hasher: &mut impl Hasher) {
+    match value.reflect_ref() {
+        ReflectRef::Struct(x) => x.iter_fields().for_each(|field| reflect_checksum(field, hasher)),
+        ReflectRef::TupleStruct(x) => x.iter_fields().for_each(|field| reflect_checksum(field, hasher)),
+        ReflectRef::Tuple(x) => x.iter_fields().for_each(|field| reflect_checksum(field, hasher)),
+        ReflectRef::List(x) => {
+            (x.len() as u64).hash(hasher);
+            x.iter().for_each(|item| reflect_checksum(item, hasher));
+        },
+        ReflectRef::Array(x) => x.iter().for_each(|item| reflect_checksum(item, hasher)),
+        ReflectRef::Map(x) => {
+            let mut sum = 0u64;
+            for (key, value) in x.iter() {
+                let mut entry = ChecksumHasher::default();
+                reflect_checksum(key, &mut entry);
+                reflect_checksum(value, &mut entry);
+                sum = sum.wrapping_add(entry.finish());
+            }
+            (x.len() as u64, sum).hash(hasher);
+        },
+        ReflectRef::Set(x) => {
+            let mut sum = 0u64;
+            for value in x.iter() {
+                let mut entry = ChecksumHasher::default();
+                reflect_checksum(value, &mut entry);
+                sum = sum.wrapping_add(entry.finish());
+            }
+            (x.len() as u64, sum).hash(hasher);
+        },
+        ReflectRef::Enum(x) => {
+            (x.variant_index() as u64).hash(hasher);
+            x.iter_fields().for_each(|field| reflect_checksum(field.value(), hasher));
+        },
+        #[allow(unreachable_patterns)]
+        _ => hash_opaque(value, hasher),
+    }
+}
+
+fn hash_opaque(value: &dyn PartialReflect, hasher: &mut impl Hasher) {
+    macro_rules! hash_as {
+        ($($T:ty),*) => {
+            $(if let Some(x) = value.try_downcast_ref::<$T>() {
+                x.hash(hasher);
+                return
+            })*
+        };
+    }
+    if let Some(x) = value.try_downcast_ref::<f32>() {
+        x.to_bits().hash(hasher);
+    }else if let Some(x) = value.try_downcast_ref::<f64>() {
+        x.to_bits().hash(hasher);
+    }else{
+        hash_as!(bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, char, String);
+        //the size of usize differs between platforms
+        if let Some(x) = value.try_downcast_ref::<usize>() {
+            (*x as u64).hash(hasher);
+            return
+        }
+        if let Some(x) = value.try_downcast_ref::<isize>() {
+            (*x as i64).hash(hasher);
+            return
+        }
+        match value.reflect_hash() {
+            Some(hash) => hash.hash(hasher),
+            None => value.reflect_type_path().hash(hasher),
+        }
+    }
+}
+
+/// Implemented for tuples of types whose snapshots can be hashed by [`checksum_component`]
+pub trait ChecksumGroup {
+    fn get_checksum<const LEN: usize>() -> Vec<SystemConfigs>;
+    fn get_checksum_option<const LEN: usize>() -> Vec<SystemConfigs>;
+}
+
+macro_rules! impl_checksum_tuple_config {
+    ($($T:ident),*) => {
+        impl<$($T: Reflect + FromReflect + TypePath + bevy::reflect::GetTypeRegistration + bevy::reflect::MaybeTyped),*> ChecksumGroup for ($($T,)*) {
+            fn get_checksum<const LEN: usize>() -> Vec<SystemConfigs> {
+                vec![$(checksum_component::<$T,LEN>.in_set(ChecksumSet::Hash),)*]
+            }
+            fn get_checksum_option<const LEN: usize>() -> Vec<SystemConfigs> {
+                vec![$(checksum_component::<Option<$T>,LEN>.in_set(ChecksumSet::Hash),)*]
+            }
+        }
+    };
+}
+
+use bevy_utils::all_tuples;
+all_tuples!(impl_checksum_tuple_config, 1, 15, T);
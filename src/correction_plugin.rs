@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::ecs::intern::Interned;
+
+use crate::*;
+use crate::schedule_plugin::*;
+
+// Corrections are authoritative snapshots of a past frame, for example sent by a server.
+// The snapshot is written into the Rollback<T> slot of the frame and the frame is marked as Modified,
+// the rollback then restores that frame and resimulates from it.
+// A resimulation from an older frame (or one which did not finish yet because of max_update_loops or the time budget)
+// would overwrite the corrected snapshot when it saves that frame again, so the correction is kept and applied again after that save.
+
+/// Consumes [`CorrectComponent<T>`] events in [`RollbackProcessSet::HandleIO`]
+pub struct RollbackCorrectionPlugin<T, const LEN: usize> {
+    /// The [`Schedule`] in which the events are consumed, it should be the same as in [`RollbackSchedulePlugin`]
+    pub rollback_processing_schedule: Interned<dyn ScheduleLabel>,
+    _type: std::marker::PhantomData<T>,
+}
+
+impl<T, const LEN: usize> Default for RollbackCorrectionPlugin<T, LEN> {
+    fn default() -> Self {
+        Self {
+            rollback_processing_schedule: Update.intern(),
+            _type: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static, const LEN: usize> Plugin for RollbackCorrectionPlugin<T, LEN> {
+    fn build(&self, app: &mut App) {
+        app
+        .add_event::<CorrectComponent<T>>()
+        .insert_resource(PendingCorrections::<T>(Vec::new()))
+        .add_systems(self.rollback_processing_schedule, correct_component_system::<T, LEN>.in_set(RollbackProcessSet::HandleIO))
+        .add_systems(RollbackSave, reapply_corrections_system::<T, LEN>.in_set(RollbackSaveSet::PostSave));
+    }
+}
+
+/// Overwrites the snapshot of `T` saved in `frame` for the entity with [`RollbackID`] `id`
+#[derive(Event, Clone, Debug)]
+pub struct CorrectComponent<T> {
+    pub frame: u64,
+    pub id: RollbackID,
+    pub value: T,
+}
+
+/// Corrections which can still be overwritten by a resimulation, as `(frame, entity, value)`.
+/// They are applied again by [`reapply_corrections_system`] whenever their frame is saved again,
+/// and dropped once their frame falls out of the rollback history.
+#[derive(Resource)]
+pub struct PendingCorrections<T>(pub Vec<(u64, Entity, T)>);
+
+/// Checks that `frame` is in the rollback history and that its slot in [`Rollback<Frame>`] was saved for it
+pub fn check_frame<const LEN: usize>(frame: u64, last_frame: u64, frames: &Rollback<Frame, LEN>) -> Result<(), RollbackEventErrorKind> {
+    let oldest_frame = last_frame.saturating_sub(frames.len() as u64 - 1);
+    if frame < oldest_frame {
+        return Err(RollbackEventErrorKind::TooOld { oldest_frame })
+    }
+    if frame > last_frame {
+        return Err(RollbackEventErrorKind::TooNew { last_frame })
+    }
+    match frames.get(frame) {
+        Some(x) if x.0 == frame => Ok(()),
+        x => Err(RollbackEventErrorKind::FrameMismatch { stored_frame: x.map(|x| x.0) }),
+    }
+}
+
+pub fn correct_component_system<T: Clone + Send + Sync + 'static, const LEN: usize>(
+    mut events: EventReader<CorrectComponent<T>>,
+    map: Res<RollbackMap>,
+    (last_frame, frames): (Res<LastFrame>, Res<Rollback<Frame, LEN>>),
+    mut pending: ResMut<PendingCorrections<T>>,
+    mut modified: ResMut<Rollback<Modified, LEN>>,
+    mut query: Query<(&mut Rollback<T, LEN>, Option<&mut systems::LastChange<T>>)>,
+    mut errors: EventWriter<RollbackEventError>,
+) {
+    for event in events.read() {
+        let result = check_frame(event.frame, last_frame.0, &frames).and_then(|_| {
+            let entity = map.entity(event.id).ok_or(RollbackEventErrorKind::UnknownRollbackID(event.id))?;
+            let (mut rollback, last_change) = query.get_mut(entity).map_err(|_| RollbackEventErrorKind::MissingStorage(event.id))?;
+            rollback.set(event.frame, event.value.clone());
+            //the entity differs from the corrected snapshot, it has to be restored by restore_dirty
+            if let Some(mut last_change) = last_change {
+                last_change.frame = last_change.frame.max(event.frame + 1);
+            }
+            pending.0.push((event.frame, entity, event.value.clone()));
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => {
+                if let Some(modified) = modified.get_mut(event.frame) {
+                    modified.0 = true;
+                }
+            },
+            Err(kind) => {
+                let type_name = std::any::type_name::<T>();
+                warn!("Could not apply correction of {type_name} in frame {}: {kind:?}", event.frame);
+                errors.send(RollbackEventError { frame: event.frame, type_name, kind });
+            },
+        }
+    }
+}
+
+/// Writes the [`PendingCorrections<T>`] of the saved [`Frame`] into its snapshots again and marks the frame as [`Modified`],
+/// [`rollback_update_system`] then restores the frame before simulating further
+pub fn reapply_corrections_system<T: Clone + Send + Sync + 'static, const LEN: usize>(
+    current_frame: Res<Frame>,
+    (last_frame, frames): (Res<LastFrame>, Res<Rollback<Frame, LEN>>),
+    mut pending: ResMut<PendingCorrections<T>>,
+    mut modified: ResMut<Rollback<Modified, LEN>>,
+    mut query: Query<(&mut Rollback<T, LEN>, Option<&mut systems::LastChange<T>>)>,
+) {
+    //a later rollback can resimulate the corrected frame again, so the correction is kept as long as its frame can be restored
+    let oldest_frame = last_frame.0.saturating_sub(frames.len() as u64 - 1);
+    pending.0.retain(|(frame, entity, value)| {
+        if *frame < oldest_frame {
+            return false
+        }
+        if *frame != current_frame.0 {
+            return true
+        }
+        let Ok((mut rollback, last_change)) = query.get_mut(*entity) else {
+            return false
+        };
+        rollback.set(*frame, value.clone());
+        if let Some(mut last_change) = last_change {
+            last_change.frame = last_change.frame.max(*frame + 1);
+        }
+        if let Some(modified) = modified.get_mut(*frame) {
+            modified.0 = true;
+        }
+        true
+    });
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::ecs::intern::Interned;
+
+use crate::*;
+use crate::schedule_plugin::*;
+
+/// Publishes [`RollbackStats`] and the prediction distance (`LastFrame - ConfirmedFrame`) as Bevy diagnostics
+pub struct RollbackDiagnosticsPlugin {
+    /// The [`Schedule`] in which the measurements are published, it should be the same as in [`RollbackSchedulePlugin`]
+    pub rollback_processing_schedule: Interned<dyn ScheduleLabel>,
+}
+
+impl Default for RollbackDiagnosticsPlugin {
+    fn default() -> Self {
+        Self { rollback_processing_schedule: Update.intern() }
+    }
+}
+
+impl RollbackDiagnosticsPlugin {
+    pub const DEPTH: DiagnosticPath = DiagnosticPath::const_new("rollback/depth");
+    pub const RESIMULATED_FRAMES: DiagnosticPath = DiagnosticPath::const_new("rollback/resimulated_frames");
+    pub const RESTORE_TIME: DiagnosticPath = DiagnosticPath::const_new("rollback/restore_time");
+    pub const UPDATE_TIME: DiagnosticPath = DiagnosticPath::const_new("rollback/update_time");
+    pub const SAVE_TIME: DiagnosticPath = DiagnosticPath::const_new("rollback/save_time");
+    pub const PREDICTION_DISTANCE: DiagnosticPath = DiagnosticPath::const_new("rollback/prediction_distance");
+}
+
+impl Plugin for RollbackDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .register_diagnostic(Diagnostic::new(Self::DEPTH))
+        .register_diagnostic(Diagnostic::new(Self::RESIMULATED_FRAMES))
+        .register_diagnostic(Diagnostic::new(Self::RESTORE_TIME).with_suffix("ms"))
+        .register_diagnostic(Diagnostic::new(Self::UPDATE_TIME).with_suffix("ms"))
+        .register_diagnostic(Diagnostic::new(Self::SAVE_TIME).with_suffix("ms"))
+        .register_diagnostic(Diagnostic::new(Self::PREDICTION_DISTANCE))
+        .add_systems(self.rollback_processing_schedule, rollback_diagnostics_system.after(RollbackProcessSet::RunRollbackSchedule));
+    }
+}
+
+pub fn rollback_diagnostics_system(
+    stats: Res<RollbackStats>,
+    last_frame: Res<LastFrame>,
+    confirmed: Res<ConfirmedFrame>,
+    mut diagnostics: Diagnostics,
+) {
+    diagnostics.add_measurement(&RollbackDiagnosticsPlugin::DEPTH, || stats.depth as f64);
+    diagnostics.add_measurement(&RollbackDiagnosticsPlugin::RESIMULATED_FRAMES, || stats.resimulated_frames as f64);
+    diagnostics.add_measurement(&RollbackDiagnosticsPlugin::RESTORE_TIME, || stats.restore_time.as_secs_f64() * 1000.0);
+    diagnostics.add_measurement(&RollbackDiagnosticsPlugin::UPDATE_TIME, || stats.update_time.as_secs_f64() * 1000.0);
+    diagnostics.add_measurement(&RollbackDiagnosticsPlugin::SAVE_TIME, || stats.save_time.as_secs_f64() * 1000.0);
+    //nothing is known about the prediction until some frame is confirmed
+    if let Some(confirmed) = confirmed.0 {
+        diagnostics.add_measurement(&RollbackDiagnosticsPlugin::PREDICTION_DISTANCE, || last_frame.0.saturating_sub(confirmed) as f64);
+    }
+}
+
+/// Publishes the memory used by all [`Rollback<T>`] storages (components and the [`Resource`]) in bytes,
+/// under the path returned by [`storage_path::<T>`]. Memory owned by `T` itself (for example in a [`Vec`]) is not counted.
+pub struct RollbackStorageDiagnosticsPlugin<T, const LEN: usize> {
+    /// The [`Schedule`] in which the measurements are published
+    pub rollback_processing_schedule: Interned<dyn ScheduleLabel>,
+    _type: std::marker::PhantomData<T>,
+}
+
+impl<T, const LEN: usize> Default for RollbackStorageDiagnosticsPlugin<T, LEN> {
+    fn default() -> Self {
+        Self {
+            rollback_processing_schedule: Update.intern(),
+            _type: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static, const LEN: usize> Plugin for RollbackStorageDiagnosticsPlugin<T, LEN> {
+    fn build(&self, app: &mut App) {
+        app
+        .register_diagnostic(Diagnostic::new(storage_path::<T>()).with_suffix("B"))
+        .add_systems(self.rollback_processing_schedule, storage_diagnostics_system::<T, LEN>);
+    }
+}
+
+/// `rollback/storage/<type name of T>`
+pub fn storage_path<T>() -> DiagnosticPath {
+    DiagnosticPath::from_components(["rollback", "storage", std::any::type_name::<T>()])
+}
+
+fn storage_size<T, const LEN: usize>(rollback: &Rollback<T, LEN>) -> usize {
+    std::mem::size_of_val(&rollback.0[..]) + std::mem::size_of_val(&rollback.1[..])
+}
+
+pub fn storage_diagnostics_system<T: Send + Sync + 'static, const LEN: usize>(
+    query: Query<&Rollback<T, LEN>>,
+    resource: Option<Res<Rollback<T, LEN>>>,
+    mut path: Local<Option<DiagnosticPath>>,
+    mut diagnostics: Diagnostics,
+) {
+    let path = path.get_or_insert_with(storage_path::<T>);
+    diagnostics.add_measurement(path, || {
+        let size = query.iter().map(storage_size).sum::<usize>() + resource.map_or(0, |x| storage_size(&x));
+        size as f64
+    });
+}
@@ -0,0 +1,92 @@
+use std::hash::Hash;
+
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use bevy::utils::HashMap;
+
+use crate::*;
+use crate::schedule_plugin::*;
+
+/// Deduplicates one-shot effects of type `K` triggered by [`EffectTrigger<K>`] inside [`RollbackUpdate`].
+/// Effects which were not triggered again when their frame was resimulated are reported by [`EffectCancelled<K>`].
+pub struct RollbackEffectPlugin<K, const LEN: usize>(std::marker::PhantomData<K>);
+
+impl<K, const LEN: usize> Default for RollbackEffectPlugin<K, LEN> {
+    fn default() -> Self {
+        Self(std::marker::PhantomData)
+    }
+}
+
+impl<K: Hash + Eq + Clone + Send + Sync + 'static, const LEN: usize> Plugin for RollbackEffectPlugin<K, LEN> {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Effects<K>>()
+        .add_event::<EffectCancelled<K>>()
+        .add_systems(RollbackUpdate, (
+            begin_effects_system::<K>.before(RollbackUpdateSet::LoadInputs),
+            end_effects_system::<K, LEN>.after(RollbackUpdateSet::Update),
+        ));
+    }
+}
+
+/// Sent when a frame was resimulated and the effect which was played in it was not triggered again
+#[derive(Event, Clone, Debug)]
+pub struct EffectCancelled<K> {
+    pub frame: u64,
+    pub id: Option<RollbackID>,
+    pub key: K,
+}
+
+/// The effects that were played in the frames of the rollback history.
+/// The value is `true` if the effect was triggered during the current simulation of its frame.
+#[derive(Resource)]
+pub struct Effects<K>(pub HashMap<(u64, Option<RollbackID>, K), bool>);
+
+impl<K> Default for Effects<K> {
+    fn default() -> Self {
+        Self(HashMap::default())
+    }
+}
+
+/// Triggers one-shot effects inside [`RollbackUpdate`]
+#[derive(SystemParam)]
+pub struct EffectTrigger<'w, K: Hash + Eq + Send + Sync + 'static> {
+    pub effects: ResMut<'w, Effects<K>>,
+    pub current_frame: Res<'w, Frame>,
+}
+
+impl<K: Hash + Eq + Send + Sync + 'static> EffectTrigger<'_, K> {
+    /// Triggers the effect `key` of the entity `id` (`None` for global effects) in the current [`Frame`].
+    /// Returns `true` if the effect should be played, that is when it was not triggered in any previous simulation of this frame.
+    pub fn trigger(&mut self, id: Option<RollbackID>, key: K) -> bool {
+        self.effects.0.insert((self.current_frame.0, id, key), true).is_none()
+    }
+}
+
+/// Marks the effects of the simulated frame as not triggered
+pub fn begin_effects_system<K: Hash + Eq + Send + Sync + 'static>(
+    current_frame: Res<Frame>,
+    mut effects: ResMut<Effects<K>>,
+) {
+    let frame = current_frame.0;
+    effects.0.iter_mut().filter(|((x, ..), _)| *x == frame).for_each(|(_, triggered)| *triggered = false);
+}
+
+/// Cancels the effects of the simulated frame which were not triggered again and forgets effects older than the rollback history
+pub fn end_effects_system<K: Hash + Eq + Clone + Send + Sync + 'static, const LEN: usize>(
+    current_frame: Res<Frame>,
+    last_frame: Res<LastFrame>,
+    frames: Res<Rollback<Frame, LEN>>,
+    mut effects: ResMut<Effects<K>>,
+    mut cancelled: EventWriter<EffectCancelled<K>>,
+) {
+    let frame = current_frame.0;
+    let oldest_frame = last_frame.0.saturating_sub(frames.len() as u64 - 1);
+    effects.0.retain(|(x, id, key), triggered| {
+        if *x == frame && !*triggered {
+            cancelled.send(EffectCancelled { frame, id: *id, key: key.clone() });
+            return false
+        }
+        *x >= oldest_frame
+    });
+}
@@ -11,23 +11,27 @@ pub struct ExistencePlugin<const LEN: usize>;
 impl<const LEN: usize> Plugin for ExistencePlugin<LEN> {
     fn build(&self, app: &mut App) {
         app
-        .add_systems(RollbackRestore, restore_exists_remove_nonexistent::<LEN, systems::DefaultFilter>)
+        .add_systems(RollbackRestore, restore_exists_remove_nonexistent::<LEN, systems::DefaultFilter>.in_set(RollbackRestoreSet::Despawn))
         .add_systems(RollbackSave, (
-            systems::save::<Exists, LEN>,
-            despawn_nonexistent::<LEN>,
-        ).chain());
+            systems::save::<Exists, LEN>.in_set(RollbackSaveSet::Components),
+            despawn_nonexistent::<LEN>.in_set(RollbackSaveSet::PostSave),
+        ));
+
+        #[cfg(feature = "growable")]
+        app.add_systems(RollbackResize, systems::resize_rollback::<Exists, LEN>);
     }
 }
 
 //can use Query<..., Changed<Exists>> to run code that handles the "virtual" despawn and respawn when needed
-#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[derive(Component, Reflect, Clone, Copy, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct Exists(pub bool);
 impl Default for Exists {
     fn default() -> Self { Exists(false) }  //TODO: maybe it should be Exists(true)
 }
 
-//only despawn entities with Rollback<Exists> having all false, thus indicating that the entity should be removed
+//only despawn entities with Rollback<Exists> having all saved slots false, thus indicating that the entity should be removed
+//an entity without any saved slot was not saved yet, it is kept
 pub fn despawn_nonexistent<const LEN: usize>(
     mut commands: Commands,
     query: Query<(Entity, &Rollback<Exists, LEN>)>,
@@ -35,7 +39,8 @@ pub fn despawn_nonexistent<const LEN: usize>(
     for (e, r) in &query {
         //TODO: use some form of caching, like NonExistentFor(frames: usize)
         //the cached value can be updated when a custom save<Exists> runs
-        if r.0.iter().all(|x| !x.0) {
+        let mut saved = r.iter_saved().peekable();
+        if saved.peek().is_some() && saved.all(|(_, x)| !x.0) {
             commands.entity(e).despawn_recursive();
         }
     }
@@ -50,20 +55,19 @@ pub fn restore_exists_remove_nonexistent<const LEN: usize, Filter: QueryFilter>(
     mut query: Query<(Entity, &mut Exists, &Rollback<Exists, LEN>), Filter>,
     mut commands: Commands,
 ) {
-    let oldest_frame = last_frame.0.saturating_sub(LEN as u64 - 1);
-    let current_index = crate::index::<LEN>(current_frame.0);
-
     'outer: for (e, mut existence, r) in &mut query {
-        let ex = r.0[current_index];
-        *existence = ex;
+        let oldest_frame = last_frame.0.saturating_sub(r.len() as u64 - 1);
+        //a slot that was never written for the frame means that the entity did not exist yet
+        let ex = r.get(current_frame.0).copied().unwrap_or(Exists(false));
+        existence.set_if_neq(ex);   //Changed<Exists> fires only when the existence changed
         if !ex.0 {
-            println!("checking despawning entity {e:?}");
-            for i in (oldest_frame..current_frame.0).map(|frame| crate::index::<LEN>(frame)) {
-                if r.0[i].0 {
+            rollback_trace!(entity = ?e, frame = current_frame.0, "Checking despawning of entity");
+            for frame in oldest_frame..current_frame.0 {
+                if r.get(frame).is_some_and(|x| x.0) {
                     continue 'outer;    //the entity exists
                 }
             }
-            println!("despawning entity {e:?}");
+            rollback_debug!(entity = ?e, frame = current_frame.0, "Despawning entity which does not exist in any saved frame");
             commands.entity(e).despawn_recursive(); //the entity does not exist, despawn it
         }
     }
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::*;
+use crate::schedule_plugin::*;
+
+// A RollbackID is split into a namespace (upper 16 bits) and an index inside the namespace (lower 48 bits).
+// The simulation allocator is a rolled back Resource, so an entity spawned in RollbackUpdate gets the same ID on every resimulation.
+// The peer allocator is not rolled back, the IDs it gave out stay in use after a rollback.
+// Namespace 0 is left for hand assigned IDs like RollbackID(0), so they never collide with the allocated ones.
+
+/// Inserts [`RollbackIdAllocator`] and saves/restores it every frame, and inserts [`LocalIdAllocator`]
+pub struct RollbackIdPlugin<const LEN: usize>;
+
+impl<const LEN: usize> Plugin for RollbackIdPlugin<LEN> {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<RollbackIdAllocator>()
+        .init_resource::<LocalIdAllocator>()
+        .init_resource::<Rollback<RollbackIdAllocator, LEN>>()
+        .add_systems(RollbackRestore, systems::restore_resource::<RollbackIdAllocator, LEN>.in_set(RollbackRestoreSet::Resources))
+        .add_systems(RollbackSave, systems::save_resource::<RollbackIdAllocator, LEN>.in_set(RollbackSaveSet::Resources));
+
+        #[cfg(feature = "growable")]
+        app.add_systems(RollbackResize, systems::resize_rollback_resource::<RollbackIdAllocator, LEN>);
+    }
+}
+
+#[derive(Reflect, Default, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdNamespace(pub u16);
+
+impl IdNamespace {
+    /// For hand assigned IDs, it is never used by [`RollbackIdAllocator`] and [`LocalIdAllocator`]
+    pub const MANUAL: Self = Self(0);
+    /// For entities spawned by the deterministic simulation, all peers allocate the same IDs in it
+    pub const SIMULATION: Self = Self(1);
+
+    /// The greatest `peer` accepted by [`IdNamespace::peer`]
+    pub const MAX_PEER: u16 = u16::MAX - 2;
+
+    /// For entities spawned locally by the peer `peer`, outside of the deterministic simulation.
+    /// Panics when `peer` is greater than [`IdNamespace::MAX_PEER`].
+    pub const fn peer(peer: u16) -> Self {
+        match peer.checked_add(2) {
+            Some(namespace) => Self(namespace),
+            None => panic!("peer is greater than IdNamespace::MAX_PEER"),
+        }
+    }
+}
+
+impl RollbackID {
+    const INDEX_BITS: u32 = 48;
+
+    pub const fn new(namespace: IdNamespace, index: u64) -> Self {
+        assert!(index < 1 << Self::INDEX_BITS, "RollbackID index out of range");
+        Self((namespace.0 as u64) << Self::INDEX_BITS | index)
+    }
+
+    pub const fn namespace(&self) -> IdNamespace {
+        IdNamespace((self.0 >> Self::INDEX_BITS) as u16)
+    }
+
+    pub const fn index(&self) -> u64 {
+        self.0 & ((1 << Self::INDEX_BITS) - 1)
+    }
+}
+
+/// Allocates unique [`RollbackID`]s in [`IdNamespace::SIMULATION`], it is rolled back by [`RollbackIdPlugin`]
+#[derive(Resource, Reflect, Default, Clone, Debug)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct RollbackIdAllocator {
+    /// The next free index
+    pub next: u64,
+}
+
+impl RollbackIdAllocator {
+    /// Returns a new unique [`RollbackID`] in [`IdNamespace::SIMULATION`]
+    pub fn allocate(&mut self) -> RollbackID {
+        let id = RollbackID::new(IdNamespace::SIMULATION, self.next);
+        self.next += 1;
+        id
+    }
+}
+
+/// Allocates unique [`RollbackID`]s in the [`IdNamespace::peer`] namespaces, it is not rolled back
+#[derive(Resource, Reflect, Default, Clone, Debug)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocalIdAllocator {
+    /// The next free index of every namespace
+    pub next: HashMap<IdNamespace, u64>,
+}
+
+impl LocalIdAllocator {
+    /// Returns a new unique [`RollbackID`] in [`IdNamespace::peer(peer)`](IdNamespace::peer)
+    pub fn allocate(&mut self, peer: u16) -> RollbackID {
+        let namespace = IdNamespace::peer(peer);
+        let next = self.next.entry(namespace).or_insert(0);
+        let id = RollbackID::new(namespace, *next);
+        *next += 1;
+        id
+    }
+}
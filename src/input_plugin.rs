@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::ecs::intern::Interned;
+
+use crate::*;
+use crate::schedule_plugin::*;
+
+// Inputs are stored per player and per frame. The inputs of frame F are used by the update from F to F+1.
+// Inputs of frames which were not simulated yet (frame >= LastFrame) are buffered and used when the frame is simulated.
+// When the input of a player is missing while simulating a frame, it is predicted and the prediction is stored,
+// once the real (confirmed) input arrives and differs from the prediction, the frame is marked as Modified.
+// The following predicted inputs are predicted again from it and their frames are marked only when the prediction changed.
+
+/// Stores inputs of type `I` for every player and frame in [`RollbackInputs<I, LEN>`] and loads the inputs
+/// of the current [`Frame`] into [`FrameInputs<I>`] in [`RollbackUpdateSet::LoadInputs`].
+/// Missing inputs are predicted by `predict`, by default the last input of the player is repeated.
+/// Confirmed inputs can be inserted by [`InputWriter`] or by sending [`InsertInput<I>`] events.
+pub struct RollbackInputPlugin<I, const LEN: usize> {
+    /// The number of players at the start, more players can be added by [`RollbackInputs::add_player`]
+    pub players: usize,
+    /// Predicts the input of a player from the input of the previous frame (if it is known)
+    pub predict: fn(Option<&I>) -> I,
+    /// The [`Schedule`] in which [`InsertInput<I>`] events are consumed, it should be the same as in [`RollbackSchedulePlugin`]
+    pub rollback_processing_schedule: Interned<dyn ScheduleLabel>,
+}
+
+impl<I: Clone + Default, const LEN: usize> Default for RollbackInputPlugin<I, LEN> {
+    fn default() -> Self {
+        Self {
+            players: 1,
+            predict: repeat_last_input,
+            rollback_processing_schedule: Update.intern(),
+        }
+    }
+}
+
+/// The default input prediction, repeats the previous input or uses `I::default()` if it is not known
+pub fn repeat_last_input<I: Clone + Default>(previous: Option<&I>) -> I {
+    previous.cloned().unwrap_or_default()
+}
+
+impl<I: Clone + Default + PartialEq + Send + Sync + 'static, const LEN: usize> Plugin for RollbackInputPlugin<I, LEN> {
+    fn build(&self, app: &mut App) {
+        let mut inputs = RollbackInputs::<I, LEN>::new(self.predict);
+        for _ in 0..self.players {
+            inputs.add_player(0);
+        }
+        app.world_mut().get_resource_or_init::<InputSources>().register(std::any::type_name::<I>());
+
+        app
+        .insert_resource(inputs)
+        .insert_resource(FrameInputs::<I>(Vec::new()))
+        .add_event::<InsertInput<I>>()
+        .add_systems(self.rollback_processing_schedule, (
+            insert_input_system::<I, LEN>,
+            report_confirmed_inputs_system::<I, LEN>,
+        ).chain().in_set(RollbackProcessSet::HandleIO))
+        .add_systems(RollbackUpdate, load_inputs_system::<I, LEN>.in_set(RollbackUpdateSet::LoadInputs));
+
+        #[cfg(feature = "growable")]
+        app.add_systems(RollbackResize, resize_inputs_system::<I, LEN>);
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerHandle(pub usize);
+
+#[derive(Reflect, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum InputStatus {
+    /// The input was not received yet, it was predicted
+    #[default]
+    Predicted,
+    /// The real input of the player
+    Confirmed,
+}
+
+#[derive(Reflect, Default, Clone, PartialEq, Debug)]
+pub struct InputSlot<I> {
+    pub input: I,
+    pub status: InputStatus,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputError {
+    UnknownPlayer(PlayerHandle),
+    /// The frame is older than the rollback history, it can not be changed anymore
+    TooOld { frame: u64, oldest_frame: u64 },
+}
+
+impl std::fmt::Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::UnknownPlayer(player) => write!(f, "unknown player {player:?}"),
+            InputError::TooOld { frame, oldest_frame } => write!(f, "frame {frame} is older than the oldest stored frame {oldest_frame}"),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+/// The inputs of one player
+pub struct PlayerInputs<I, const LEN: usize> {
+    /// Inputs of the frames which were already simulated
+    pub inputs: Rollback<InputSlot<I>, LEN>,
+    /// Confirmed inputs of the frames which were not simulated yet
+    pub future: BTreeMap<u64, I>,
+    /// The first frame in which the player has inputs
+    pub start_frame: u64,
+    /// All inputs from `start_frame` up to this frame are confirmed
+    pub confirmed_frame: Option<u64>,
+}
+
+impl<I: Clone + PartialEq, const LEN: usize> PlayerInputs<I, LEN> {
+    pub fn status(&self, frame: u64) -> Option<InputStatus> {
+        if self.future.contains_key(&frame) {
+            Some(InputStatus::Confirmed)
+        }else{
+            self.inputs.get(frame).map(|x| x.status)
+        }
+    }
+
+    fn update_confirmed_frame(&mut self) {
+        let mut next = self.confirmed_frame.map_or(self.start_frame, |x| x+1);
+        while self.status(next) == Some(InputStatus::Confirmed) {
+            self.confirmed_frame = Some(next);
+            next += 1;
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct RollbackInputs<I, const LEN: usize> {
+    pub players: Vec<PlayerInputs<I, LEN>>,
+    pub predict: fn(Option<&I>) -> I,
+}
+
+impl<I: Clone + Default + PartialEq, const LEN: usize> RollbackInputs<I, LEN> {
+    pub fn new(predict: fn(Option<&I>) -> I) -> Self {
+        Self { players: Vec::new(), predict }
+    }
+
+    /// Adds a player which has inputs from `start_frame`
+    pub fn add_player(&mut self, start_frame: u64) -> PlayerHandle {
+        self.players.push(PlayerInputs {
+            inputs: Rollback::default(),
+            future: BTreeMap::new(),
+            start_frame,
+            confirmed_frame: None,
+        });
+        PlayerHandle(self.players.len()-1)
+    }
+
+    pub fn player(&self, player: PlayerHandle) -> Option<&PlayerInputs<I, LEN>> {
+        self.players.get(player.0)
+    }
+
+    /// The status of the input of `player` in `frame`, `None` if the input is not known and was not predicted yet
+    pub fn status(&self, player: PlayerHandle, frame: u64) -> Option<InputStatus> {
+        self.player(player)?.status(frame)
+    }
+
+    /// The newest frame up to which all inputs of all players are confirmed
+    pub fn confirmed_frame(&self) -> Option<u64> {
+        self.players.iter().map(|x| x.confirmed_frame).min().flatten()
+    }
+
+    /// Inserts the confirmed input of `player` in `frame`.
+    /// Returns `Ok(true)` if the input differs from what was used when simulating `frame`,
+    /// in that case the frame has to be marked as [`Modified`], [`InputWriter`] does that automatically.
+    pub fn confirm(&mut self, frame: u64, player: PlayerHandle, input: I, last_frame: u64) -> Result<bool, InputError> {
+        let player = self.players.get_mut(player.0).ok_or(InputError::UnknownPlayer(player))?;
+
+        //frame >= last_frame was not simulated yet
+        if frame >= last_frame {
+            player.future.insert(frame, input);
+            player.update_confirmed_frame();
+            return Ok(false)
+        }
+
+        let oldest_frame = last_frame.saturating_sub(player.inputs.len() as u64 - 1);
+        if frame < oldest_frame {
+            return Err(InputError::TooOld { frame, oldest_frame })
+        }
+
+        let changed = player.inputs.get(frame).is_none_or(|x| x.input != input);
+        player.inputs.set(frame, InputSlot { input, status: InputStatus::Confirmed });
+        player.update_confirmed_frame();
+        Ok(changed)
+    }
+}
+
+/// Inserts confirmed inputs into [`RollbackInputs<I, LEN>`] and marks changed frames as [`Modified`]
+#[derive(SystemParam)]
+pub struct InputWriter<'w, I: Send + Sync + 'static, const LEN: usize> {
+    pub inputs: ResMut<'w, RollbackInputs<I, LEN>>,
+    pub last_frame: Res<'w, LastFrame>,
+    pub modified: ResMut<'w, Rollback<Modified, LEN>>,
+}
+
+impl<I: Clone + Default + PartialEq + Send + Sync + 'static, const LEN: usize> InputWriter<'_, I, LEN> {
+    pub fn confirm(&mut self, frame: u64, player: PlayerHandle, input: I) -> Result<(), InputError> {
+        if self.inputs.confirm(frame, player, input, self.last_frame.0)? {
+            if let Some(modified) = self.modified.get_mut(frame) {
+                modified.0 = true;
+            }
+            //the following predicted inputs were predicted from the old input, they are predicted again
+            //and only the frames whose prediction changed are marked, so an unchanged resimulation can still converge
+            let predict = self.inputs.predict;
+            let inputs = &mut self.inputs.players[player.0].inputs;
+            for frame in frame+1..self.last_frame.0 {
+                let input = predict(inputs.get(frame-1).map(|x| &x.input));
+                let Some(slot) = inputs.get_mut(frame) else {break};
+                if slot.status != InputStatus::Predicted || slot.input == input {
+                    break
+                }
+                slot.input = input;
+                if let Some(modified) = self.modified.get_mut(frame) {
+                    modified.0 = true;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Inserts the confirmed input of `player` in `frame`, consumed in [`RollbackProcessSet::HandleIO`].
+/// Inputs which can not be inserted are reported by [`RollbackEventError`].
+#[derive(Event, Clone, Debug)]
+pub struct InsertInput<I> {
+    pub frame: u64,
+    pub player: PlayerHandle,
+    pub data: I,
+}
+
+pub fn insert_input_system<I: Clone + Default + PartialEq + Send + Sync + 'static, const LEN: usize>(
+    mut events: EventReader<InsertInput<I>>,
+    mut writer: InputWriter<I, LEN>,
+    mut errors: EventWriter<RollbackEventError>,
+) {
+    for event in events.read() {
+        if let Err(e) = writer.confirm(event.frame, event.player, event.data.clone()) {
+            let type_name = std::any::type_name::<I>();
+            warn!("Could not insert input {type_name}: {e}");
+            let kind = match e {
+                InputError::UnknownPlayer(player) => RollbackEventErrorKind::UnknownPlayer(player),
+                InputError::TooOld { oldest_frame, .. } => RollbackEventErrorKind::TooOld { oldest_frame },
+            };
+            errors.send(RollbackEventError { frame: event.frame, type_name, kind });
+        }
+    }
+}
+
+/// Reports [`RollbackInputs::confirmed_frame`] to [`InputSources`]
+pub fn report_confirmed_inputs_system<I: Clone + Default + PartialEq + Send + Sync + 'static, const LEN: usize>(
+    inputs: Res<RollbackInputs<I, LEN>>,
+    mut sources: ResMut<InputSources>,
+) {
+    sources.report(std::any::type_name::<I>(), inputs.confirmed_frame());
+}
+
+/// The inputs of all players for the current [`Frame`], indexed by [`PlayerHandle`]
+#[derive(Resource, Deref, DerefMut)]
+pub struct FrameInputs<I>(pub Vec<InputSlot<I>>);
+
+impl<I> FrameInputs<I> {
+    pub fn get(&self, player: PlayerHandle) -> Option<&InputSlot<I>> {
+        self.0.get(player.0)
+    }
+}
+
+pub fn load_inputs_system<I: Clone + Default + PartialEq + Send + Sync + 'static, const LEN: usize>(
+    current_frame: Res<Frame>,
+    mut inputs: ResMut<RollbackInputs<I, LEN>>,
+    mut frame_inputs: ResMut<FrameInputs<I>>,
+) {
+    let frame = current_frame.0;
+    let predict = inputs.predict;
+    frame_inputs.0.clear();
+    for player in &mut inputs.players {
+        if let Some(input) = player.future.remove(&frame) {
+            player.inputs.set(frame, InputSlot { input, status: InputStatus::Confirmed });
+        }
+        let slot = match player.inputs.get(frame) {
+            Some(slot) if slot.status == InputStatus::Confirmed => slot.clone(),
+            //predicted inputs are predicted again, the previous input could have been confirmed in the meantime
+            _ => {
+                let previous = frame.checked_sub(1).and_then(|x| player.inputs.get(x)).map(|x| &x.input);
+                let slot = InputSlot { input: predict(previous), status: InputStatus::Predicted };
+                player.inputs.set(frame, slot.clone());
+                slot
+            },
+        };
+        frame_inputs.0.push(slot);
+    }
+}
+
+#[cfg(feature = "growable")]
+pub fn resize_inputs_system<I: Send + Sync + 'static, const LEN: usize>(
+    length: Res<HistoryLength>,
+    last_frame: Res<LastFrame>,
+    mut inputs: ResMut<RollbackInputs<I, LEN>>,
+) {
+    for player in &mut inputs.players {
+        if player.inputs.len() != length.0 {
+            player.inputs.resize(length.0, last_frame.0);
+        }
+    }
+}
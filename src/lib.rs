@@ -1,15 +1,62 @@
+// All debug output of this crate goes through these macros. The spans and events have the target of the module
+// they come from (for example `bevy_gravirollback::schedule_plugin`), so their levels are set by the filter of LogPlugin,
+// for example "bevy_gravirollback=debug" for a span of every rollback pass or "bevy_gravirollback=trace" for every frame.
+// With the `no_debug_output` feature they expand to nothing and the crate produces no debug output at all.
+
+/// Enters a span which is exited when the returned guard is dropped: `let _span = rollback_span!(debug_span, "name", frame);`
+#[cfg(not(feature = "no_debug_output"))]
+macro_rules! rollback_span {
+    ($span:ident, $($arg:tt)+) => { bevy::log::$span!($($arg)+).entered() };
+}
+#[cfg(feature = "no_debug_output")]
+macro_rules! rollback_span {
+    ($span:ident, $($arg:tt)+) => { () };
+}
+
+#[cfg(not(feature = "no_debug_output"))]
+macro_rules! rollback_debug {
+    ($($arg:tt)+) => { bevy::log::debug!($($arg)+) };
+}
+#[cfg(feature = "no_debug_output")]
+macro_rules! rollback_debug {
+    ($($arg:tt)+) => { () };
+}
+
+#[cfg(not(feature = "no_debug_output"))]
+macro_rules! rollback_trace {
+    ($($arg:tt)+) => { bevy::log::trace!($($arg)+) };
+}
+#[cfg(feature = "no_debug_output")]
+macro_rules! rollback_trace {
+    ($($arg:tt)+) => { () };
+}
+
 pub mod rollback_config_plugin;
 
 pub mod schedule_plugin;
 pub mod existence_plugin;
 pub mod systems;
 pub mod for_user;
+pub mod checksum_plugin;
+pub mod sync_test_plugin;
+pub mod input_plugin;
+pub mod correction_plugin;
+pub mod effect_plugin;
+pub mod rng_plugin;
+pub mod id_plugin;
+pub mod reflect_plugin;
+pub mod recompute_plugin;
+pub mod diagnostics_plugin;
 
 use bevy::prelude::*;
 use bevy::ecs::component::ComponentId;
 use bevy::ecs::world::DeferredWorld;
+use bevy::ecs::system::SystemParam;
 use bevy::utils::HashMap;
 
+use std::fmt::Debug;
+use std::hash::Hash;
+
 pub mod prelude {
     pub use crate::*;
     pub use crate::systems::*;
@@ -17,6 +64,16 @@ pub mod prelude {
     pub use crate::schedule_plugin::*;
     pub use crate::existence_plugin::*;
     pub use crate::rollback_config_plugin::*;
+    pub use crate::checksum_plugin::*;
+    pub use crate::sync_test_plugin::*;
+    pub use crate::input_plugin::*;
+    pub use crate::correction_plugin::*;
+    pub use crate::effect_plugin::*;
+    pub use crate::rng_plugin::*;
+    pub use crate::id_plugin::*;
+    pub use crate::reflect_plugin::*;
+    pub use crate::recompute_plugin::*;
+    pub use crate::diagnostics_plugin::*;
 }
 
 // *****************************
@@ -43,99 +100,268 @@ pub mod prelude {
 // so you should be able to rollback the rollbacks
 // or said differently: have a simulation of some rollback enabled system (not in the sense of ECS system), and the simulation itself has rollback
 
-//TODO: component change detection should work correctly even when changing frames -> maybe use marker Component to signal change?
+//component change detection: restore_if_neq writes only the components that differ from the restored snapshot,
+//and RestoreChanges tells apart the changes made by the restore from the changes made by the simulation
 
-//TODO: figure out how to remove the Default requirement for #[reflect(Resource)]
+/// Storage of snapshots of `T`, one slot for each frame in the rollback history.
+/// The slot of a frame is found by [`Rollback::index`], the storage is used as a ring buffer.
+/// Every slot is tagged with the frame that was saved into it (second field), `None` means that the slot was never written.
+/// A slot which was never written holds no value, so `T` does not need to implement [`Default`].
+///
+/// By default the size is fixed to `LEN`. With the `growable` feature the storage is backed by a [`Vec`]
+/// and `LEN` is only the initial length, the actual length can be changed at runtime with [`HistoryLength`].
+#[cfg(not(feature = "growable"))]
+#[derive(Component, Resource, Reflect, Deref, DerefMut, Clone)]
+#[reflect(Resource)]
+pub struct Rollback<T, const LEN: usize>(#[deref] pub [Option<T>; LEN], pub [Option<u64>; LEN]);   //this version has fixed size, it should be faster as there is no pointer dereference, question is if it matters or was it just premature optimization
+#[cfg(not(feature = "growable"))]
+impl<T, const LEN: usize> Default for Rollback<T, LEN> {
+    fn default() -> Self {
+        Self(std::array::from_fn(|_| None), [None; LEN])     //this had to be done manualy because the #[derive(Default)] macro could not handle it with big LEN
+    }
+}
 
-//TODO: use a flag to choose if the size is growable, size is fixed by default
+/// Storage of snapshots of `T`, one slot for each frame in the rollback history.
+/// The slot of a frame is found by [`Rollback::index`], the storage is used as a ring buffer.
+/// Every slot is tagged with the frame that was saved into it (second field), `None` means that the slot was never written.
+/// A slot which was never written holds no value, so `T` does not need to implement [`Default`].
+///
+/// This version is growable during runtime, `LEN` is only the initial length.
+/// A [`Component`] storage is resized to [`HistoryLength`] when it is added to an entity,
+/// after that the length is kept in sync by the systems in the [`RollbackResize`] [`Schedule`].
+#[cfg(feature = "growable")]
 #[derive(Component, Resource, Reflect, Deref, DerefMut, Clone)]
+#[component(on_add=rollback_on_add::<T, LEN>)]
 #[reflect(Resource)]
-pub struct Rollback<T, const LEN: usize>(pub [T; LEN]);   //this version has fixed size, it should be faster as there is no pointer dereference, question is if it matters or was it just premature optimization
-impl<T: Default, const LEN: usize> Default for Rollback<T, LEN> {
+pub struct Rollback<T, const LEN: usize>(#[deref] pub Vec<Option<T>>, pub Vec<Option<u64>>);
+#[cfg(feature = "growable")]
+impl<T, const LEN: usize> Default for Rollback<T, LEN> {
     fn default() -> Self {
-        Self(std::array::from_fn(|_| T::default()))     //this had to be done manualy because the #[derive(Default)] macro could not handle it with big LEN
+        Self::with_len(LEN)
+    }
+}
+
+impl<T, const LEN: usize> Rollback<T, LEN> {
+    /// Storage with the snapshot `value` of `frame`, all the other slots are empty
+    pub fn new(frame: u64, value: T) -> Self {
+        let mut rollback = Self::default();
+        rollback.set(frame, value);
+        rollback
+    }
+
+    /// The index of the slot in which the snapshot of `frame` is stored
+    pub fn index(&self, frame: u64) -> usize {
+        (frame%self.0.len() as u64) as usize
+    }
+
+    /// The snapshot of `frame`, `None` if its slot was never written or it was overwritten by a different frame
+    pub fn get(&self, frame: u64) -> Option<&T> {
+        let index = self.index(frame);
+        (self.1[index] == Some(frame)).then(|| self.0[index].as_ref()).flatten()
+    }
+
+    /// The snapshot of `frame`, `None` if its slot was never written or it was overwritten by a different frame
+    pub fn get_mut(&mut self, frame: u64) -> Option<&mut T> {
+        let index = self.index(frame);
+        (self.1[index] == Some(frame)).then(|| self.0[index].as_mut()).flatten()
+    }
+
+    /// Save the snapshot of `frame` into its slot and tag the slot with `frame`
+    pub fn set(&mut self, frame: u64, value: T) {
+        let index = self.index(frame);
+        self.0[index] = Some(value);
+        self.1[index] = Some(frame);
+    }
+
+    /// Iterate over all written slots as `(frame, snapshot)`, in the order of the slots
+    pub fn iter_saved(&self) -> impl Iterator<Item = (u64, &T)> {
+        self.1.iter().zip(self.0.iter()).filter_map(|(frame, x)| frame.zip(x.as_ref()))
+    }
+}
+
+#[cfg(feature = "growable")]
+impl<T, const LEN: usize> Rollback<T, LEN> {
+    /// Empty storage of `len` slots
+    pub fn with_len(len: usize) -> Self {
+        Self((0..len).map(|_| None).collect(), vec![None; len])
+    }
+
+    /// Changes the number of stored frames. The snapshots of the newest frames up to `last_frame` are kept,
+    /// the slots which did not exist before are empty.
+    pub fn resize(&mut self, len: usize, last_frame: u64) {
+        let old_len = self.0.len();
+        if len == old_len {
+            return
+        }
+        let mut old = std::mem::replace(self, Self::with_len(len));
+        let kept = len.min(old_len) as u64;
+        for frame in (last_frame+1).saturating_sub(kept)..=last_frame {
+            let (old_index, new_index) = (old.index(frame), self.index(frame));
+            self.0[new_index] = old.0[old_index].take();
+            self.1[new_index] = old.1[old_index];
+        }
     }
 }
 
-// use std::collections::VecDeque;
-// struct Rollback<T>(VecDeque<T>);  //this version is dynamicaly growable during runtime
+/// Resizes a newly added [`Rollback<T>`] component to [`HistoryLength`], the storages created by [`Rollback::default`] have `LEN` slots
+#[cfg(feature = "growable")]
+fn rollback_on_add<T, const LEN: usize>(mut world: DeferredWorld, entity: Entity, _component_id: ComponentId) where Rollback<T, LEN>: Component {
+    let Some(length) = world.get_resource::<HistoryLength>().map(|x| x.0) else {return};
+    let last_frame = world.get_resource::<LastFrame>().map_or(0, |x| x.0);
+    let Some(mut rollback) = world.get_mut::<Rollback<T, LEN>>(entity) else {return};
+    if rollback.0.len() != length {
+        //the storage can already hold a snapshot of a frame newer than LastFrame, for example when it was created by Rollback::new
+        let newest_frame = rollback.1.iter().flatten().copied().max().unwrap_or(0).max(last_frame);
+        rollback.resize(length, newest_frame);
+    }
+}
 
+/// The number of frames stored in the rollback history. Changing it will resize every [`Rollback`] storage
+/// at the start of the next [`rollback_restore_system`](schedule_plugin::rollback_restore_system).
+/// Insert it before adding the [`RollbackPlugin`] to choose the initial length, otherwise `LEN` is used.
+/// The length has to be at least 1, a length of 0 set at runtime is rejected and replaced by the current length.
+#[cfg(feature = "growable")]
+#[derive(Resource, Reflect, Clone, Copy, Debug)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct HistoryLength(pub usize);
 
-#[derive(Component, Reflect, Clone, Copy, Hash, PartialEq, Eq, Debug)]
-#[component(on_insert=rollback_id_on_insert,on_replace=rollback_id_on_replace)]
+#[derive(Component, Reflect, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[component(on_insert=rollback_id_on_insert::<Self>,on_replace=rollback_id_on_replace::<Self>)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
-pub struct RollbackID(pub u64); //this should be user defined
+pub struct RollbackID(pub u64); //this should be user defined, unique IDs can be allocated by RollbackIdAllocator
+
+/// Bounds of the ID types that can be used in [`RollbackMap<ID>`], implemented for every type satisfying them
+pub trait MapId: Component + Copy + Hash + Eq + Debug {}
+impl<T: Component + Copy + Hash + Eq + Debug> MapId for T {}
 
 //automatically update the RollbackMap when a new RollbackID component is added or removed
-fn rollback_id_on_insert(mut world: DeferredWorld, entity: Entity, _component_id: ComponentId) {
-    let id = world.entity(entity).get::<RollbackID>().unwrap().clone();
-    world.resource_mut::<RollbackMap>().insert(entity,id);
+/// Component hook which adds the entity into [`RollbackMap<ID>`].
+/// Use it for your own ID type like this: `#[component(on_insert=rollback_id_on_insert::<Self>, on_replace=rollback_id_on_replace::<Self>)]`
+pub fn rollback_id_on_insert<ID: MapId>(mut world: DeferredWorld, entity: Entity, _component_id: ComponentId) {
+    let id = *world.entity(entity).get::<ID>().unwrap();
+    if let Err(e) = world.resource_mut::<RollbackMap<ID>>().insert(entity, id) {
+        error!("Could not add Entity {entity:?} with {id:?} into RollbackMap: {e}");
+        world.send_event(e);
+    }
 }
-fn rollback_id_on_replace(mut world: DeferredWorld, entity: Entity, _component_id: ComponentId) {
-    world.resource_mut::<RollbackMap>().remove(entity);
+/// Component hook which removes the entity from [`RollbackMap<ID>`], see [`rollback_id_on_insert`]
+pub fn rollback_id_on_replace<ID: MapId>(mut world: DeferredWorld, entity: Entity, _component_id: ComponentId) {
+    match world.resource_mut::<RollbackMap<ID>>().remove(entity) {
+        Ok(_) => (),
+        //the insert of this entity could have failed, that was already reported
+        Err(RollbackMapError::NotMapped(_)) => rollback_debug!(?entity, "Entity was not in RollbackMap"),
+        Err(e) => {
+            error!("Could not remove Entity {entity:?} from RollbackMap: {e}");
+            world.send_event(e);
+        },
+    }
+}
+
+/// Adds the [`RollbackMap<ID>`] and the [`RollbackMapError<ID>`] [`Event`]. It is added by [`RollbackPlugin`] for [`RollbackID`].
+/// The `ID` component should use the [`rollback_id_on_insert`] and [`rollback_id_on_replace`] hooks.
+pub struct RollbackMapPlugin<ID>(std::marker::PhantomData<ID>);
+
+impl<ID> Default for RollbackMapPlugin<ID> {
+    fn default() -> Self {
+        Self(std::marker::PhantomData)
+    }
+}
+
+impl<ID: MapId> Plugin for RollbackMapPlugin<ID> {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<RollbackMap<ID>>()
+        .add_event::<RollbackMapError<ID>>();
+    }
 }
 
-#[derive(Resource, Reflect, Default)]
+/// Sent by the component hooks when an entity could not be added into or removed from [`RollbackMap<ID>`]
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RollbackMapError<ID = RollbackID> {
+    /// The ID is already used by another entity
+    DuplicateId { entity: Entity, id: ID, existing: Entity },
+    /// The entity is already mapped to another ID
+    AlreadyMapped { entity: Entity, id: ID, existing: ID },
+    /// The entity has no mapping
+    NotMapped(Entity),
+    /// The entity is mapped to the ID but the ID is not mapped back to the entity
+    Inconsistent { entity: Entity, id: ID, mapped: Option<Entity> },
+}
+
+impl<ID: Debug> std::fmt::Display for RollbackMapError<ID> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RollbackMapError::DuplicateId { entity, id, existing } => write!(f, "{id:?} of Entity {entity:?} is already used by Entity {existing:?}"),
+            RollbackMapError::AlreadyMapped { entity, id, existing } => write!(f, "Entity {entity:?} can not be mapped to {id:?}, it is already mapped to {existing:?}"),
+            RollbackMapError::NotMapped(entity) => write!(f, "Entity {entity:?} is not mapped"),
+            RollbackMapError::Inconsistent { entity, id, mapped } => write!(f, "Entity {entity:?} is mapped to {id:?} but it is mapped to {mapped:?}"),
+        }
+    }
+}
+
+impl<ID: Debug> std::error::Error for RollbackMapError<ID> {}
+
+/// Two way mapping between the rollback `ID`s and [`Entity`]s
+#[derive(Resource, Reflect)]
 #[reflect(Resource)]
-pub struct RollbackMap(pub HashMap<RollbackID, Entity>, pub HashMap<Entity, RollbackID>);   //TODO: this should be generic over RollbackID
-impl RollbackMap {
-    pub fn remove(&mut self, e: Entity) {
-        self.print();
-        println!("Removing from RollbackMap {e:?}");
-        if let Some(r) = self.1.get(&e) {
-            if let Some(e2) = self.0.get(r) {
-                if *e2!=e {
-                    panic!("Entity {e:?} RollbackID {r:?} had mapping to Entity {e2:?}");
-                }
-                self.0.remove(r);
+pub struct RollbackMap<ID = RollbackID>(pub HashMap<ID, Entity>, pub HashMap<Entity, ID>);
+
+impl<ID: MapId> Default for RollbackMap<ID> {
+    fn default() -> Self {
+        Self(HashMap::default(), HashMap::default())
+    }
+}
+
+impl<ID: MapId> RollbackMap<ID> {
+    pub fn entity(&self, id: ID) -> Option<Entity> {
+        self.0.get(&id).copied()
+    }
+
+    pub fn id(&self, entity: Entity) -> Option<ID> {
+        self.1.get(&entity).copied()
+    }
+
+    /// Removes the mapping of the entity and returns its ID
+    pub fn remove(&mut self, e: Entity) -> Result<ID, RollbackMapError<ID>> {
+        let r = *self.1.get(&e).ok_or(RollbackMapError::NotMapped(e))?;
+        match self.0.get(&r) {
+            Some(e2) if *e2 == e => {
+                self.0.remove(&r);
                 self.1.remove(&e);
-            }else{
-                panic!("Entity {e:?} did not have a mapping from RollbackID {r:?}");
-            }
-        }else{
-            warn!("Entity {e:?} did not have a mapping to RollbackID");
-        }
-        self.print();
-    }
-    pub fn insert(&mut self, e: Entity, r: RollbackID) {
-        self.print();
-        println!("Adding to RollbackMap {e:?} {r:?}");
-        match (self.0.get(&r), self.1.get(&e)) {
-            (None, None) => {
-                self.0.insert(r, e);
-                self.1.insert(e, r);
+                Ok(r)
             },
-            (Some(e2), Some(r2)) => {
-                //if *e2!=e || *r2!=r {
-                    panic!("Can not add rollback mapping for Entity {e:?} RollbackID {r:?} because {e2:?}, {r2:?} already existed");
-                //}
-            },
-            //TODO: these are misleading, e2 has a RollbackID with it, so its not incomplete but (e2, RollbackID for e2) entry already exists
-            (Some(e2), None) => panic!("RollbackMap was incomplete Entity {e2:?} (insert with {e:?}, {r:?})"),
-            (None, Some(r2)) => panic!("RollbackMap was incomplete RollbackID {r2:?} (insert with {e:?}, {r:?})"),
+            e2 => Err(RollbackMapError::Inconsistent { entity: e, id: r, mapped: e2.copied() }),
         }
-        self.print();
-    }
-    pub fn print(&self) {
-        //*
-        let mut tmp = self.1.clone();
-        let x = self.0.iter().map(|(r,e)| (r.clone(),e.clone(),tmp.remove(e))).collect::<Vec<(RollbackID, Entity, Option<RollbackID>)>>();
-        println!("\tRollbackMap:");
-        for (r,e,r2) in x {
-            if let Some(r2) = r2 {
-                if r==r2 {
-                    println!("\tRollbackID {r:?} <=> Entity {e:?}");
-                }else{
-                    println!("\tRollbackID {r:?} -> Entity {e:?} -> {r2:?}");
-                }
-            }else{
-                println!("\tRollbackID {r:?} -> Entity {e:?}");
-            }
+    }
+
+    /// Adds the mapping, nothing is changed when the entity or the ID is already mapped
+    pub fn insert(&mut self, e: Entity, r: ID) -> Result<(), RollbackMapError<ID>> {
+        if let Some(e2) = self.0.get(&r) {
+            return Err(RollbackMapError::DuplicateId { entity: e, id: r, existing: *e2 })
         }
-        for (e,r) in tmp {
-            println!("\tRollbackID {r:?} <- Entity {e:?}");
+        if let Some(r2) = self.1.get(&e) {
+            return Err(RollbackMapError::AlreadyMapped { entity: e, id: r, existing: *r2 })
         }
-        // */
+        self.0.insert(r, e);
+        self.1.insert(e, r);
+        Ok(())
+    }
+}
+
+/// Read only lookup in [`RollbackMap<ID>`]
+#[derive(SystemParam)]
+pub struct RollbackLookup<'w, ID: MapId = RollbackID> {
+    pub map: Res<'w, RollbackMap<ID>>,
+}
+
+impl<ID: MapId> RollbackLookup<'_, ID> {
+    pub fn entity(&self, id: ID) -> Option<Entity> {
+        self.map.entity(id)
+    }
+
+    pub fn id(&self, entity: Entity) -> Option<ID> {
+        self.map.id(entity)
     }
 }
 
@@ -157,6 +383,62 @@ pub struct Frame(pub u64);
 #[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct LastFrame(pub u64);
 
+/// The newest frame for which every input source has reported its confirmed input, see [`InputSources`].
+/// `None` until all input sources confirmed their first frame.
+/// Once the inputs of a frame are confirmed and the frame was simulated, its update can not change anymore
+/// and [`FrameFinalized`] is sent for it.
+/// When the inputs of a source are not confirmed before their frame leaves the rollback history, it stops advancing,
+/// this is reported by [`check_confirmed_frames_system`](schedule_plugin::check_confirmed_frames_system).
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfirmedFrame(pub Option<u64>);
+
+/// The newest frame for which [`FrameFinalized`] was sent
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct FinalizedFrame(pub Option<u64>);
+
+/// Sent once for every frame, in order, when the update of the frame (from `frame` to `frame+1`) was simulated with confirmed inputs only.
+/// Everything that happened in [`RollbackUpdate`](schedule_plugin::RollbackUpdate) while [`Frame`] was `frame` will not be rolled back anymore,
+/// so irreversible side effects (scoring, achievements, persistent logging) can be triggered.
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FrameFinalized(pub u64);
+
+/// The newest confirmed frame of every input source, [`ConfirmedFrame`] is the oldest of them.
+/// [`RollbackInputPlugin`](input_plugin::RollbackInputPlugin) registers itself, other sources of inputs
+/// (for example inputs stored in [`Rollback`] Resources) should be registered and reported by the user.
+/// Without any registered source no frame is confirmed.
+#[derive(Resource, Default, Debug)]
+pub struct InputSources(pub HashMap<&'static str, Option<u64>>);
+
+impl InputSources {
+    /// Registers a source which has not confirmed any frame yet, does nothing if it is already registered
+    pub fn register(&mut self, source: &'static str) {
+        self.0.entry(source).or_insert(None);
+    }
+
+    /// Reports that `source` has confirmed inputs up to `frame` (inclusive)
+    pub fn report(&mut self, source: &'static str, frame: Option<u64>) {
+        self.0.insert(source, frame);
+    }
+
+    pub fn remove(&mut self, source: &'static str) {
+        self.0.remove(source);
+    }
+
+    /// The newest frame that all sources confirmed
+    pub fn confirmed_frame(&self) -> Option<u64> {
+        if self.0.is_empty() {
+            return None
+        }
+        self.0.values().copied().min().flatten()
+    }
+}
+
+/// The index of the slot of `frame` in a fixed size [`Rollback<T, LEN>`].
+/// With the `growable` feature use [`Rollback::index`] instead.
 pub fn index<const LEN: usize>(frame: u64) -> usize {
     (frame%LEN as u64) as usize
 }
@@ -164,6 +446,54 @@ pub fn index<const LEN: usize>(frame: u64) -> usize {
 #[derive(Reflect, Default, Clone, Copy, Debug)]
 pub struct Modified(pub bool);
 
+/// What the restore systems should do when the [`Rollback`] slot of the restored [`Frame`] was never written,
+/// or it was overwritten by a different frame. This happens for example with entities spawned after the restored frame.
+#[derive(Resource, Reflect, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[reflect(Resource)]
+pub enum MissingSnapshotPolicy {
+    /// Treat the slot as if it contained no snapshot.
+    /// Components stored in `Rollback<T>` are left untouched, components stored in `Rollback<Option<T>>` are removed.
+    Skip,
+    /// Keep the current value of the component or resource.
+    #[default]
+    KeepCurrent,
+    /// Despawn the entity.
+    Despawn,
+}
+
+/// Sent by the restore systems when the [`Rollback`] slot of the restored [`Frame`] was never written
+#[derive(Event, Clone, Copy, Debug)]
+pub struct MissingSnapshot {
+    /// The entity whose snapshot is missing, `None` for resources
+    pub entity: Option<Entity>,
+    pub frame: u64,
+    pub type_name: &'static str,
+}
+
+/// Sent when a rollback event, like [`InsertInput`](input_plugin::InsertInput) or [`CorrectComponent`](correction_plugin::CorrectComponent),
+/// could not be applied
+#[derive(Event, Clone, Copy, Debug)]
+pub struct RollbackEventError {
+    pub frame: u64,
+    /// The type carried by the event
+    pub type_name: &'static str,
+    pub kind: RollbackEventErrorKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RollbackEventErrorKind {
+    /// The frame is older than the rollback history, it can not be changed anymore
+    TooOld { oldest_frame: u64 },
+    /// The frame was not simulated yet
+    TooNew { last_frame: u64 },
+    /// The slot of the frame in [`Rollback<Frame>`] contains a different frame
+    FrameMismatch { stored_frame: Option<u64> },
+    UnknownPlayer(input_plugin::PlayerHandle),
+    UnknownRollbackID(RollbackID),
+    /// The entity does not have the [`Rollback`] storage of the type
+    MissingStorage(RollbackID),
+}
+
 pub struct RollbackPlugin<const LEN: usize>;
 
 //TODO: use *_system for names of systems probably?
@@ -173,9 +503,26 @@ impl<const LEN: usize> Plugin for RollbackPlugin<LEN> {
         .init_resource::<Frame>()
         //.init_resource::<Index>()
         .init_resource::<LastFrame>()
+        .init_resource::<ConfirmedFrame>()
+        .init_resource::<FinalizedFrame>()
+        .init_resource::<InputSources>()
+        .add_event::<FrameFinalized>()
         .init_resource::<Rollback<Frame, LEN>>()
         .init_resource::<Rollback<Modified, LEN>>()
+        .init_resource::<MissingSnapshotPolicy>()
+        .add_event::<MissingSnapshot>()
+        .add_event::<RollbackEventError>()
+
+        .add_plugins(RollbackMapPlugin::<RollbackID>::default());
 
-        .init_resource::<RollbackMap>();
+        #[cfg(feature = "growable")]
+        {
+            let world = app.world_mut();
+            let len = world.get_resource::<HistoryLength>().map(|x| x.0).unwrap_or(LEN);
+            assert!(len > 0, "HistoryLength has to be at least 1");
+            world.insert_resource(HistoryLength(len));
+            world.insert_resource(Rollback::<Frame, LEN>::with_len(len));
+            world.insert_resource(Rollback::<Modified, LEN>::with_len(len));
+        }
     }
 }
@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+use bevy::ecs::schedule::ScheduleLabel;
+
+use crate::*;
+use crate::schedule_plugin::*;
+
+// Bevy recomputes GlobalTransform (and the user recomputes other derived data) in PostUpdate, which does not run
+// between the frames of a resimulation. Without recomputing, RollbackUpdate would read the values derived from
+// the state before the restore, or from the previous frame, and the resimulated frames would differ from the original ones.
+
+/// Runs the [`RollbackRecompute`] [`Schedule`] after the restore in [`RollbackRestoreSet::PostRestore`]
+/// and after every update step (after [`RollbackUpdateSet::Update`]).
+/// By default it also propagates the [`Transform`]s of rollback entities into their [`GlobalTransform`]s.
+pub struct RollbackRecomputePlugin {
+    /// Add [`propagate_rollback_transforms`] into [`RollbackRecomputeSet::Transforms`]
+    pub propagate_transforms: bool,
+}
+
+impl Default for RollbackRecomputePlugin {
+    fn default() -> Self {
+        Self { propagate_transforms: true }
+    }
+}
+
+impl Plugin for RollbackRecomputePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_schedule(RollbackRecompute)
+        .configure_sets(RollbackRecompute, (RollbackRecomputeSet::Transforms, RollbackRecomputeSet::Derived).chain())
+        .add_systems(RollbackRestore, run_recompute_schedule.in_set(RollbackRestoreSet::PostRestore))
+        .add_systems(RollbackUpdate, run_recompute_schedule.after(RollbackUpdateSet::Update));
+
+        if self.propagate_transforms {
+            app.add_systems(RollbackRecompute, propagate_rollback_transforms.in_set(RollbackRecomputeSet::Transforms));
+        }
+    }
+}
+
+/// Recompute the state derived from the rolled back state, for example caches or spatial indexes.
+/// It is run by [`RollbackRecomputePlugin`] after every restore and update step.
+#[derive(ScheduleLabel, Hash, Debug, PartialEq, Eq, Clone)]
+pub struct RollbackRecompute;
+
+/// This [`SystemSet`] normally runs in [`RollbackRecompute`] [`Schedule`]
+#[derive(SystemSet, Hash, Debug, PartialEq, Eq, Clone)]
+pub enum RollbackRecomputeSet {
+    /// Propagate [`Transform`]s into [`GlobalTransform`]s
+    Transforms,
+    /// Recompute your own derived state here, [`GlobalTransform`]s are already up to date
+    Derived,
+}
+
+pub fn run_recompute_schedule(world: &mut World) {
+    world.run_schedule(RollbackRecompute);
+}
+
+/// Updates the [`GlobalTransform`] of every entity with [`RollbackID`] and of all its descendants.
+/// The [`GlobalTransform`] of a parent without [`RollbackID`] is taken as it is.
+pub fn propagate_rollback_transforms(
+    roots: Query<(Entity, Option<&Parent>), With<RollbackID>>,
+    mut transforms: Query<(&Transform, &mut GlobalTransform, Option<&Children>)>,
+) {
+    for (entity, parent) in &roots {
+        let parent = match parent {
+            //the entity is updated together with its parent
+            Some(parent) if roots.contains(parent.get()) => continue,
+            Some(parent) => transforms.get(parent.get()).map_or(GlobalTransform::IDENTITY, |(_, global, _)| *global),
+            None => GlobalTransform::IDENTITY,
+        };
+        propagate_recursive(entity, parent, &mut transforms);
+    }
+}
+
+fn propagate_recursive(
+    entity: Entity,
+    parent: GlobalTransform,
+    transforms: &mut Query<(&Transform, &mut GlobalTransform, Option<&Children>)>,
+) {
+    let Ok((transform, mut global, children)) = transforms.get_mut(entity) else {return};
+    let new_global = parent.mul_transform(*transform);
+    global.set_if_neq(new_global);
+    let Some(children) = children.map(|x| x.to_vec()) else {return};
+    for child in children {
+        propagate_recursive(child, new_global, transforms);
+    }
+}
@@ -0,0 +1,177 @@
+use std::any::TypeId;
+
+use bevy::prelude::*;
+use bevy::reflect::TypeRegistry;
+use bevy::ecs::system::SystemState;
+
+use crate::*;
+use crate::schedule_plugin::*;
+
+// Instead of one restore and one save system per type, the types registered in ReflectRollbackRegistry are
+// saved and restored through reflection by a single exclusive system each.
+// The snapshot of an entity holds one value for every registered component type, in the order of the registry.
+// A missing component is stored as None and it is removed on restore, like with Rollback<Option<T>>.
+
+/// Saves and restores every type in [`ReflectRollbackRegistry`] by [`reflect_save_system`] and [`reflect_restore_system`].
+/// Component snapshots are stored in [`Rollback<ReflectSnapshot>`] of every entity with [`RollbackID`] (it is inserted automatically),
+/// resource snapshots are stored in the [`Rollback<ReflectSnapshot>`] [`Resource`].
+///
+/// The types have to be registered in the [`AppTypeRegistry`] with [`ReflectComponent`] or [`ReflectResource`].
+pub struct ReflectRollbackPlugin<const LEN: usize>;
+
+impl<const LEN: usize> Plugin for ReflectRollbackPlugin<LEN> {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<ReflectRollbackRegistry>()
+        .init_resource::<Rollback<ReflectSnapshot, LEN>>()
+        .add_systems(RollbackRestore, reflect_restore_system::<LEN>.in_set(RollbackRestoreSet::Components))
+        .add_systems(RollbackSave, reflect_save_system::<LEN>.in_set(RollbackSaveSet::Components));
+
+        #[cfg(feature = "growable")]
+        app.add_systems(RollbackResize, (
+            systems::resize_rollback::<ReflectSnapshot, LEN>,
+            systems::resize_rollback_resource::<ReflectSnapshot, LEN>,
+        ));
+    }
+}
+
+/// The types saved and restored by [`ReflectRollbackPlugin`]
+#[derive(Resource, Default, Clone, Debug)]
+pub struct ReflectRollbackRegistry {
+    pub components: Vec<TypeId>,
+    pub resources: Vec<TypeId>,
+}
+
+impl ReflectRollbackRegistry {
+    pub fn add_component<T: Component>(&mut self) -> &mut Self {
+        self.add_component_id(TypeId::of::<T>())
+    }
+
+    pub fn add_resource<T: Resource>(&mut self) -> &mut Self {
+        self.add_resource_id(TypeId::of::<T>())
+    }
+
+    /// Registers the component with `type_id`, it can be used for types which are known only from the [`AppTypeRegistry`]
+    pub fn add_component_id(&mut self, type_id: TypeId) -> &mut Self {
+        if !self.components.contains(&type_id) {
+            self.components.push(type_id);
+        }
+        self
+    }
+
+    pub fn add_resource_id(&mut self, type_id: TypeId) -> &mut Self {
+        if !self.resources.contains(&type_id) {
+            self.resources.push(type_id);
+        }
+        self
+    }
+}
+
+/// The values of the registered types in one frame, `None` when the component or resource did not exist.
+/// The values are in the order of [`ReflectRollbackRegistry::components`] (or [`ReflectRollbackRegistry::resources`]).
+#[derive(Default, Debug)]
+pub struct ReflectSnapshot(pub Vec<Option<Box<dyn PartialReflect>>>);
+
+fn get_type_data<D: bevy::reflect::TypeData + Clone>(registry: &TypeRegistry, types: &[TypeId]) -> Vec<Option<D>> {
+    types.iter().map(|type_id| {
+        let data = registry.get_type_data::<D>(*type_id).cloned();
+        if data.is_none() {
+            warn_once!("Type {type_id:?} in ReflectRollbackRegistry is not registered with {}", std::any::type_name::<D>());
+        }
+        data
+    }).collect()
+}
+
+pub fn reflect_save_system<const LEN: usize>(world: &mut World) {
+    let frame = world.resource::<Frame>().0;
+    let registry = world.resource::<ReflectRollbackRegistry>().clone();
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    let components = get_type_data::<ReflectComponent>(&type_registry, &registry.components);
+    let mut query = world.query_filtered::<Entity, With<RollbackID>>();
+    let entities = query.iter(world).collect::<Vec<_>>();
+    for entity in entities {
+        let entity_ref = world.entity(entity);
+        let snapshot = components.iter().map(|reflect| {
+            reflect.as_ref().and_then(|reflect| reflect.reflect(entity_ref)).map(|x| x.clone_value())
+        }).collect();
+
+        let snapshot = ReflectSnapshot(snapshot);
+        if let Some(mut rollback) = world.get_mut::<Rollback<ReflectSnapshot, LEN>>(entity) {
+            rollback.set(frame, snapshot);
+            continue
+        }
+        #[cfg(not(feature = "growable"))]
+        let mut rollback = Rollback::<ReflectSnapshot, LEN>::default();
+        #[cfg(feature = "growable")]
+        let mut rollback = Rollback::<ReflectSnapshot, LEN>::with_len(world.resource::<HistoryLength>().0);
+        rollback.set(frame, snapshot);
+        world.entity_mut(entity).insert(rollback);
+    }
+
+    let resources = get_type_data::<ReflectResource>(&type_registry, &registry.resources);
+    let snapshot = resources.iter().map(|reflect| {
+        reflect.as_ref().and_then(|reflect| reflect.reflect(world)).map(|x| x.clone_value())
+    }).collect();
+    world.resource_mut::<Rollback<ReflectSnapshot, LEN>>().set(frame, ReflectSnapshot(snapshot));
+}
+
+pub fn reflect_restore_system<const LEN: usize>(world: &mut World, missing: &mut SystemState<systems::MissingSnapshots<'static>>) {
+    let frame = world.resource::<Frame>().0;
+    let policy = *world.resource::<MissingSnapshotPolicy>();
+    let registry = world.resource::<ReflectRollbackRegistry>().clone();
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    let components = get_type_data::<ReflectComponent>(&type_registry, &registry.components);
+    let mut query = world.query::<(Entity, &Rollback<ReflectSnapshot, LEN>)>();
+    let snapshots = query.iter(world).map(|(entity, rollback)| {
+        let values = rollback.get(frame).map(|snapshot| snapshot.0.iter().map(|x| x.as_ref().map(|x| x.clone_value())).collect::<Vec<_>>());
+        (entity, values)
+    }).collect::<Vec<_>>();
+
+    for (entity, values) in snapshots {
+        let values = match values {
+            Some(values) => values,
+            None => match missing.get_mut(world).report::<ReflectSnapshot>(Some(entity), frame) {
+                MissingSnapshotPolicy::Skip => Vec::new(),
+                MissingSnapshotPolicy::KeepCurrent => continue,
+                MissingSnapshotPolicy::Despawn => {
+                    //the entity could have been despawned already together with its despawned parent
+                    if let Ok(entity_mut) = world.get_entity_mut(entity) {
+                        entity_mut.despawn_recursive();
+                    }
+                    continue
+                },
+            },
+        };
+
+        let Ok(mut entity_mut) = world.get_entity_mut(entity) else {continue};
+        for (i, reflect) in components.iter().enumerate() {
+            let Some(reflect) = reflect else {continue};
+            match values.get(i) {
+                Some(Some(value)) => reflect.apply_or_insert(&mut entity_mut, value.as_ref(), &type_registry),
+                Some(None) => reflect.remove(&mut entity_mut),
+                //the type was registered after this snapshot was saved
+                None if policy == MissingSnapshotPolicy::Skip => reflect.remove(&mut entity_mut),
+                None => (),
+            }
+        }
+    }
+
+    let resources = get_type_data::<ReflectResource>(&type_registry, &registry.resources);
+    let values = world.resource::<Rollback<ReflectSnapshot, LEN>>().get(frame)
+        .map(|snapshot| snapshot.0.iter().map(|x| x.as_ref().map(|x| x.clone_value())).collect::<Vec<_>>());
+    let Some(values) = values else {
+        missing.get_mut(world).report::<ReflectSnapshot>(None, frame);
+        return
+    };
+    for (reflect, value) in resources.iter().zip(values) {
+        let Some(reflect) = reflect else {continue};
+        match value {
+            Some(value) => reflect.apply_or_insert(world, value.as_ref(), &type_registry),
+            None => reflect.remove(world),
+        }
+    }
+}
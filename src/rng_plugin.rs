@@ -0,0 +1,135 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+
+use crate::*;
+use crate::schedule_plugin::*;
+
+// The random generators are part of the rolled back state, so the resimulation of a frame draws the same numbers.
+// The generator is SplitMix64, it is small (single u64 state), fast and its output is the same on every platform.
+// Entity generators are seeded from the seed of RollbackRng and the RollbackID, so they do not depend on the spawn order.
+
+/// Inserts [`RollbackRng`] seeded with `seed` and saves/restores it every frame.
+/// Use [`RollbackRng`] or [`EntityRng`] inside [`RollbackUpdateSet::Update`] instead of non deterministic randomness.
+pub struct RollbackRngPlugin<const LEN: usize> {
+    /// All peers have to use the same seed
+    pub seed: u64,
+}
+
+impl<const LEN: usize> Default for RollbackRngPlugin<LEN> {
+    fn default() -> Self {
+        Self { seed: 0 }
+    }
+}
+
+impl<const LEN: usize> Plugin for RollbackRngPlugin<LEN> {
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(RollbackRng::new(self.seed))
+        .init_resource::<Rollback<RollbackRng, LEN>>()
+        .add_systems(RollbackRestore, systems::restore_resource::<RollbackRng, LEN>.in_set(RollbackRestoreSet::Resources))
+        .add_systems(RollbackSave, systems::save_resource::<RollbackRng, LEN>.in_set(RollbackSaveSet::Resources));
+
+        #[cfg(feature = "growable")]
+        app.add_systems(RollbackResize, systems::resize_rollback_resource::<RollbackRng, LEN>);
+    }
+}
+
+/// Deterministic pseudo random number generator (SplitMix64)
+#[derive(Reflect, Default, Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeterministicRng {
+    pub state: u64,
+}
+
+impl DeterministicRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        mix(self.state)
+    }
+
+    pub fn u32(&mut self) -> u32 {
+        (self.u64() >> 32) as u32
+    }
+
+    /// Uniform in `[0, 1)`
+    pub fn f32(&mut self) -> f32 {
+        (self.u64() >> 40) as f32 * (1.0 / (1u64 << 24) as f32)
+    }
+
+    /// Uniform in `[0, 1)`
+    pub fn f64(&mut self) -> f64 {
+        (self.u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// `true` with the probability `p`
+    pub fn bool(&mut self, p: f64) -> bool {
+        self.f64() < p
+    }
+
+    /// Uniform in `range`, panics if the range is empty
+    pub fn range_u64(&mut self, range: Range<u64>) -> u64 {
+        assert!(range.start < range.end, "empty range");
+        let len = range.end - range.start;
+        //rejection sampling, so that the result is not biased towards the small values
+        let zone = u64::MAX - u64::MAX % len;
+        loop {
+            let x = self.u64();
+            if x < zone {
+                return range.start + x % len
+            }
+        }
+    }
+
+    /// Uniform in `range`, panics if the range is empty
+    pub fn range_i64(&mut self, range: Range<i64>) -> i64 {
+        assert!(range.start < range.end, "empty range");
+        let len = range.end.wrapping_sub(range.start) as u64;
+        range.start.wrapping_add(self.range_u64(0..len) as i64)
+    }
+
+    /// Uniform in `range`
+    pub fn range_f32(&mut self, range: Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.f32()
+    }
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// The global random generator of the simulation, it is rolled back by [`RollbackRngPlugin`]
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug, Deref, DerefMut)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct RollbackRng {
+    #[deref]
+    pub rng: DeterministicRng,
+    /// The seed this generator was created with, used for seeding [`EntityRng`]
+    pub seed: u64,
+}
+
+impl RollbackRng {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: DeterministicRng::new(seed), seed }
+    }
+
+    /// Creates the generator of the entity with `id`. It is the same no matter when it is created,
+    /// so entities spawned during a resimulation get the same generator again.
+    pub fn for_entity(&self, id: RollbackID) -> EntityRng {
+        EntityRng(DeterministicRng::new(mix(self.seed ^ mix(id.0))))
+    }
+}
+
+/// The random generator of a single entity, created by [`RollbackRng::for_entity`].
+/// It has to be registered for rollback like any other [`Component`], for example by
+/// [`RollbackSystemConfigurator::add`](crate::rollback_config_plugin::RollbackSystemConfigurator::add).
+#[derive(Component, Reflect, Default, Clone, Copy, Debug, Deref, DerefMut)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct EntityRng(pub DeterministicRng);
@@ -1,18 +1,24 @@
 use bevy::prelude::*;
 use bevy::ecs::query::QueryFilter;
 use bevy::ecs::schedule::ScheduleLabel;
-use bevy::ecs::schedule::SystemConfigs;
+use bevy::ecs::schedule::{InternedSystemSet, SystemConfigs};
 use bevy::ecs::intern::Interned;
+use bevy::reflect::GetTypeRegistration;
 
 use crate::*;
 use crate::schedule_plugin::*;
 use crate::systems::*;
+use crate::checksum_plugin::*;
 
 pub struct RollbackSystemConfigurator<const LEN: usize> {
     pub restore: Option<Interned<dyn ScheduleLabel>>,
     pub save: Option<Interned<dyn ScheduleLabel>>,
     restore_systems: Vec<SystemConfigs>,
     save_systems: Vec<SystemConfigs>,
+    checksum_systems: Vec<SystemConfigs>,
+    #[cfg(feature = "growable")]
+    resize_systems: Vec<SystemConfigs>,
+    registrations: Vec<Registration>,
 }
 
 impl<const LEN: usize> Default for RollbackSystemConfigurator<LEN> {
@@ -22,6 +28,10 @@ impl<const LEN: usize> Default for RollbackSystemConfigurator<LEN> {
             save: Some(RollbackSave.intern()),
             restore_systems: vec![],
             save_systems: vec![],
+            checksum_systems: vec![],
+            #[cfg(feature = "growable")]
+            resize_systems: vec![],
+            registrations: vec![],
         }
     }
 }
@@ -35,26 +45,132 @@ impl<const LEN: usize> RollbackSystemConfigurator<LEN> {
     pub fn apply(&mut self, app: &mut App) {
         self.restore.map(|schedule| self.restore_systems.drain(..).for_each(|system| {app.add_systems(schedule, system);}));
         self.save.map(|schedule| self.save_systems.drain(..).for_each(|system| {app.add_systems(schedule, system);}));
+        self.checksum_systems.drain(..).for_each(|system| {app.add_systems(RollbackChecksum, system);});
+        #[cfg(feature = "growable")]
+        self.resize_systems.drain(..).for_each(|system| {app.add_systems(RollbackResize, system);});
+        self.registrations.drain(..).for_each(|registration| registration(app));
     }
 
-    pub fn add<T: RollbackCapableGroup>(&mut self) -> &mut Self {
+    pub fn add<T: RollbackCapableGroup + ResizableGroup>(&mut self) -> &mut Self {
         self.restore_systems.extend(T::get_restore::<LEN,DefaultFilter>());
         self.save_systems.extend(T::get_save::<LEN,DefaultFilter>());
+        #[cfg(feature = "growable")]
+        self.resize_systems.extend(T::get_resize::<LEN>());
         self
     }
     pub fn add_option<T: RollbackCapableGroup>(&mut self) -> &mut Self {
         self.restore_systems.extend(T::get_restore_option::<LEN,DefaultFilter>());
         self.save_systems.extend(T::get_save_option::<LEN,DefaultFilter>());
+        #[cfg(feature = "growable")]
+        self.resize_systems.extend(T::get_resize_option::<LEN>());
         self
     }
-    pub fn add_filter<T: RollbackCapableGroup, Filter: QueryFilter + 'static>(&mut self) -> &mut Self {
+    pub fn add_filter<T: RollbackCapableGroup + ResizableGroup, Filter: QueryFilter + 'static>(&mut self) -> &mut Self {
         self.restore_systems.extend(T::get_restore::<LEN,Filter>());
         self.save_systems.extend(T::get_save::<LEN,Filter>());
+        #[cfg(feature = "growable")]
+        self.resize_systems.extend(T::get_resize::<LEN>());
         self
     }
     pub fn add_option_filter<T: RollbackCapableGroup, Filter: QueryFilter + 'static>(&mut self) -> &mut Self {
         self.restore_systems.extend(T::get_restore_option::<LEN,Filter>());
         self.save_systems.extend(T::get_save_option::<LEN,Filter>());
+        #[cfg(feature = "growable")]
+        self.resize_systems.extend(T::get_resize_option::<LEN>());
+        self
+    }
+
+    /// Like [`add`](Self::add) for a single component, but the component is written on restore only when
+    /// the restored value differs, so [`Changed<T>`] does not fire for components which the rollback did not change
+    pub fn add_if_neq<T: Component + Clone + PartialEq>(&mut self) -> &mut Self {
+        self.restore_systems.push(restore_if_neq::<T,LEN>.in_set(RollbackRestoreSet::Components));
+        self.save_systems.push(save::<T,LEN>.in_set(RollbackSaveSet::Components));
+        #[cfg(feature = "growable")]
+        self.resize_systems.push(resize_rollback::<T,LEN>.into_configs());
+        self
+    }
+    /// Like [`add_option`](Self::add_option) for a single component, but the component is written on restore only when the restored value differs
+    pub fn add_option_if_neq<T: Component + Clone + PartialEq>(&mut self) -> &mut Self {
+        self.restore_systems.push(restore_option_if_neq::<T,LEN>.in_set(RollbackRestoreSet::Components));
+        self.save_systems.push(save_option::<T,LEN>.in_set(RollbackSaveSet::Components));
+        #[cfg(feature = "growable")]
+        self.resize_systems.push(resize_rollback::<Option<T>,LEN>.into_configs());
+        self
+    }
+
+    /// Like [`add`](Self::add) for a single component, but with dirty tracking: [`save_dirty`] records the newest frame
+    /// in which the component changed and [`restore_dirty`] restores only the entities which changed since the restored frame
+    pub fn add_dirty<T: Component + Clone>(&mut self) -> &mut Self {
+        self.restore_systems.push(restore_dirty::<T,LEN>.in_set(RollbackRestoreSet::Components));
+        self.save_systems.push(save_dirty::<T,LEN>.in_set(RollbackSaveSet::Components));
+        #[cfg(feature = "growable")]
+        self.resize_systems.push(resize_rollback::<T,LEN>.into_configs());
+        self
+    }
+
+    /// Add the [`Resource`] `R` stored in the [`Rollback<R>`] [`Resource`], which is inserted by [`apply`](Self::apply).
+    /// `R` itself has to be inserted by you.
+    pub fn add_resource<R: Resource + Clone>(&mut self) -> &mut Self {
+        self.restore_systems.push(restore_resource::<R,LEN>.in_set(RollbackRestoreSet::Resources));
+        self.save_systems.push(save_resource::<R,LEN>.in_set(RollbackSaveSet::Resources));
+        #[cfg(feature = "growable")]
+        self.resize_systems.push(resize_rollback_resource::<R,LEN>.into_configs());
+        self.registrations.push(Box::new(|app| {app.init_resource::<Rollback<R, LEN>>();}));
+        self
+    }
+    /// Add the [`Resource`] `R` stored in the [`Rollback<Option<R>>`] [`Resource`], which is inserted by [`apply`](Self::apply).
+    /// `R` is removed on restore when it did not exist in the restored frame.
+    pub fn add_resource_option<R: Resource + Clone>(&mut self) -> &mut Self {
+        self.restore_systems.push(restore_resource_option::<R,LEN>.in_set(RollbackRestoreSet::Resources));
+        self.save_systems.push(save_resource_option::<R,LEN>.in_set(RollbackSaveSet::Resources));
+        #[cfg(feature = "growable")]
+        self.resize_systems.push(resize_rollback_resource::<Option<R>,LEN>.into_configs());
+        self.registrations.push(Box::new(|app| {app.init_resource::<Rollback<Option<R>, LEN>>();}));
+        self
+    }
+    /// Add the input `I` stored in the [`Rollback<Option<I>>`] [`Resource`], it is loaded into the `I` [`Resource`] in [`RollbackUpdateSet::LoadInputs`].
+    /// The inputs are written into [`Rollback<Option<I>>`], the input of every new frame is cleared to `None` in [`RollbackSave`]
+    /// and `I` is removed while a frame without an input is simulated. Use [`add_input_default`](Self::add_input_default) for inputs with a default.
+    pub fn add_input<I: Resource + Clone>(&mut self) -> &mut Self {
+        #[cfg(feature = "growable")]
+        self.resize_systems.push(resize_rollback_resource::<Option<I>,LEN>.into_configs());
+        self.registrations.push(Box::new(|app| {
+            app
+            .init_resource::<Rollback<Option<I>, LEN>>()
+            .add_systems(RollbackUpdate, restore_resource_option::<I,LEN>.in_set(RollbackUpdateSet::LoadInputs))
+            .add_systems(RollbackSave, clear_resource_input_option::<I,LEN>.in_set(RollbackSaveSet::Resources));
+        }));
+        self
+    }
+    /// Add the input `I` stored in the [`Rollback<I>`] [`Resource`], it is loaded into the `I` [`Resource`] in [`RollbackUpdateSet::LoadInputs`].
+    /// The input of every new frame is cleared to `I::default()` in [`RollbackSave`] and `I` is inserted by [`apply`](Self::apply).
+    pub fn add_input_default<I: Resource + Clone + Default>(&mut self) -> &mut Self {
+        #[cfg(feature = "growable")]
+        self.resize_systems.push(resize_rollback_resource::<I,LEN>.into_configs());
+        self.registrations.push(Box::new(|app| {
+            app
+            .init_resource::<I>()
+            .init_resource::<Rollback<I, LEN>>()
+            .add_systems(RollbackUpdate, restore_resource::<I,LEN>.in_set(RollbackUpdateSet::LoadInputs))
+            .add_systems(RollbackSave, clear_resource_input_default::<I,LEN>.in_set(RollbackSaveSet::Resources));
+        }));
+        self
+    }
+
+    /// Add the saved snapshots of `T` into the [`Checksum`], requires the [`ChecksumPlugin`]
+    pub fn add_checksum<T: ChecksumGroup>(&mut self) -> &mut Self {
+        self.checksum_systems.extend(T::get_checksum::<LEN>());
+        self
+    }
+    /// Add the saved snapshots of `Option<T>` into the [`Checksum`], requires the [`ChecksumPlugin`]
+    pub fn add_checksum_option<T: ChecksumGroup>(&mut self) -> &mut Self {
+        self.checksum_systems.extend(T::get_checksum_option::<LEN>());
+        self
+    }
+    /// Add the saved snapshots of the [`Resource`] `T` into the [`Checksum`], requires the [`ChecksumPlugin`].
+    /// Use `Option<T>` for resources stored in `Rollback<Option<T>>`.
+    pub fn add_resource_checksum<T: PartialReflect + TypePath>(&mut self) -> &mut Self {
+        self.checksum_systems.push(checksum_resource::<T,LEN>.in_set(ChecksumSet::Hash));
         self
     }
 }
@@ -64,22 +180,49 @@ pub trait RollbackCapableGroup {
     fn get_restore_option<const LEN: usize, Filter: QueryFilter + 'static>() -> Vec<SystemConfigs>;
     fn get_save<const LEN: usize, Filter: QueryFilter + 'static>() -> Vec<SystemConfigs>;
     fn get_save_option<const LEN: usize, Filter: QueryFilter + 'static>() -> Vec<SystemConfigs>;
+    #[cfg(feature = "growable")]
+    fn get_resize_option<const LEN: usize>() -> Vec<SystemConfigs>;
 }
 
+/// Implemented for groups of types which can be stored in a growable [`Rollback`].
+/// Without the `growable` feature this is implemented for every type.
+pub trait ResizableGroup {
+    #[cfg(feature = "growable")]
+    fn get_resize<const LEN: usize>() -> Vec<SystemConfigs>;
+}
+
+#[cfg(not(feature = "growable"))]
+impl<T> ResizableGroup for T {}
+
 macro_rules! impl_rollback_capable_tuple_config {
     ($($T:ident),*) => {
         impl<$($T: RollbackCapable),*> RollbackCapableGroup for ($($T,)*) {
             fn get_restore<const LEN: usize, Filter: QueryFilter + 'static>() -> Vec<SystemConfigs> {
-                vec![$(systems::restore_filter::<$T,LEN,Filter>.into_configs(),)*]
+                vec![$(systems::restore_filter::<$T,LEN,Filter>.in_set(RollbackRestoreSet::Components),)*]
             }
             fn get_restore_option<const LEN: usize, Filter: QueryFilter + 'static>() -> Vec<SystemConfigs> {
-                vec![$(systems::restore_option_filter::<$T,LEN,Filter>.into_configs(),)*]
+                vec![$(systems::restore_option_filter::<$T,LEN,Filter>.in_set(RollbackRestoreSet::Components),)*]
             }
             fn get_save<const LEN: usize, Filter: QueryFilter + 'static>() -> Vec<SystemConfigs> {
-                vec![$(systems::save_filter::<$T,LEN,Filter>.into_configs(),)*]
+                vec![$(systems::save_filter::<$T,LEN,Filter>.in_set(RollbackSaveSet::Components),)*]
             }
             fn get_save_option<const LEN: usize, Filter: QueryFilter + 'static>() -> Vec<SystemConfigs> {
-                vec![$(systems::save_option_filter::<$T,LEN,Filter>.into_configs(),)*]
+                vec![$(systems::save_option_filter::<$T,LEN,Filter>.in_set(RollbackSaveSet::Components),)*]
+            }
+            #[cfg(feature = "growable")]
+            fn get_resize_option<const LEN: usize>() -> Vec<SystemConfigs> {
+                vec![$(systems::resize_rollback::<Option<$T>,LEN>.into_configs(),)*]
+            }
+        }
+    };
+}
+
+#[cfg(feature = "growable")]
+macro_rules! impl_resizable_tuple_config {
+    ($($T:ident),*) => {
+        impl<$($T: RollbackCapable),*> ResizableGroup for ($($T,)*) {
+            fn get_resize<const LEN: usize>() -> Vec<SystemConfigs> {
+                vec![$(systems::resize_rollback::<$T,LEN>.into_configs(),)*]
             }
         }
     };
@@ -87,6 +230,8 @@ macro_rules! impl_rollback_capable_tuple_config {
 
 use bevy_utils::all_tuples;
 all_tuples!(impl_rollback_capable_tuple_config, 1, 15, T);
+#[cfg(feature = "growable")]
+all_tuples!(impl_resizable_tuple_config, 1, 15, T);
 
 /*
 pub trait RollbackSystems {
@@ -116,156 +261,357 @@ impl<T: RollbackCapable> RollbackSystems for T {
 }
 */
 
-//experimental work in progress
-/*
-
-pub trait RollbackStorage: Send + Sync {
-    fn restore(self: Box<Self>, entity_mut: &mut EntityWorldMut);  //TODO: can this be moved to RollbackRegistry?
+/// Builder which registers rollback types together with their storage, reflection and systems in one place.
+/// Every type gets the default restore and save systems unless they are replaced.
+/// ```ignore
+/// RollbackConfig::<LEN>::new()
+///     .register_component::<Transform>()
+///         .set_restore(my_transform_restore_system)
+///         .set_save(my_transform_save_system)
+///         .merging_policy(my_transform_merge_system)
+///     .register_component_option::<Health>()
+///         .filter::<With<Player>>()
+///         .checksum()
+///     .register_resource::<PlayerScore>()
+///     .register_input::<MyInput>()
+///         .set_restore(my_input_restore_system)
+///     .register_input_default::<MyDefaultInput>()
+///     .apply(&mut app);
+/// ```
+/// Entities still need the [`Rollback<T>`] component for every registered component, see [`make_rollback`](crate::for_user::make_rollback).
+pub struct RollbackConfig<const LEN: usize> {
+    registrations: Vec<Registration>,
 }
-impl<T: Default + 'static + Clone + Send + Sync> RollbackStorage for Rollback<T> {
-    fn restore(self: Box<Self>, entity_mut: &mut EntityWorldMut) {
-        entity_mut.insert(*self);
+
+type Registration = Box<dyn FnOnce(&mut App)>;
+
+impl<const LEN: usize> Default for RollbackConfig<LEN> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-#[derive(Resource)]
-pub struct RollbackRegistry {
-    pub getters: Vec<Getter>,  //TODO: maybe it should be a HashMap<ComponentId, fn(...)->...> or similar for optimization
-}
+impl<const LEN: usize> RollbackConfig<LEN> {
+    pub fn new() -> Self {
+        Self { registrations: Vec::new() }
+    }
 
-pub type Getter = fn(&mut EntityWorldMut) -> Option<Box<dyn RollbackStorage>>;
+    pub fn apply(self, app: &mut App) {
+        for registration in self.registrations {
+            registration(app);
+        }
+    }
 
+    /// Component stored in [`Rollback<T>`], restored in [`RollbackRestore`] and saved in [`RollbackSave`]
+    pub fn register_component<T>(self) -> RollbackTypeConfig<T, ComponentType, LEN>
+    where
+        T: RollbackCapable + GetTypeRegistration,
+        (T,): ResizableGroup,
+        Rollback<T, LEN>: GetTypeRegistration,
+    {
+        RollbackTypeConfig::new(self, RollbackRestore.intern(), RollbackRestoreSet::Components.intern(), RollbackSaveSet::Components.intern(),
+            restore::<T, LEN>.into_configs(),
+            Some(save::<T, LEN>.into_configs()),
+            |app| {
+                app.register_type::<T>().register_type::<Rollback<T, LEN>>();
+                #[cfg(feature = "growable")]
+                <(T,)>::get_resize::<LEN>().into_iter().for_each(|system| {app.add_systems(RollbackResize, system);});
+            },
+        )
+    }
 
-pub fn getter<T: Clone + Default + 'static + Send + Sync>(entity: &mut EntityWorldMut) -> Option<Box<dyn RollbackStorage>> {    //TODO: remove Default requirement
-    entity.take::<Rollback<T>>().map(|x| Box::new(x) as Box<dyn RollbackStorage>)
-}
+    /// Component stored in [`Rollback<Option<T>>`], it is removed on restore when it did not exist in the restored frame
+    pub fn register_component_option<T>(self) -> RollbackTypeConfig<T, OptionComponentType, LEN>
+    where
+        T: RollbackCapable + GetTypeRegistration,
+        Rollback<Option<T>, LEN>: GetTypeRegistration,
+    {
+        RollbackTypeConfig::new(self, RollbackRestore.intern(), RollbackRestoreSet::Components.intern(), RollbackSaveSet::Components.intern(),
+            restore_option::<T, LEN>.into_configs(),
+            Some(save_option::<T, LEN>.into_configs()),
+            |app| {
+                app.register_type::<T>().register_type::<Rollback<Option<T>, LEN>>();
+                #[cfg(feature = "growable")]
+                <(T,)>::get_resize_option::<LEN>().into_iter().for_each(|system| {app.add_systems(RollbackResize, system);});
+            },
+        )
+    }
 
-use std::marker::PhantomData;
-use bevy::ecs::schedule::SystemConfigs;
+    /// Resource stored in the [`Rollback<T>`] [`Resource`], restored in [`RollbackRestore`] and saved in [`RollbackSave`].
+    /// `T` itself has to be inserted by you.
+    pub fn register_resource<T>(self) -> RollbackTypeConfig<T, ResourceType, LEN>
+    where
+        T: Resource + Clone + GetTypeRegistration,
+        Rollback<T, LEN>: GetTypeRegistration,
+    {
+        RollbackTypeConfig::new(self, RollbackRestore.intern(), RollbackRestoreSet::Resources.intern(), RollbackSaveSet::Resources.intern(),
+            restore_resource::<T, LEN>.into_configs(),
+            Some(save_resource::<T, LEN>.into_configs()),
+            |app| {
+                app
+                .init_resource::<Rollback<T, LEN>>()
+                .register_type::<T>()
+                .register_type::<Rollback<T, LEN>>();
+                #[cfg(feature = "growable")]
+                app.add_systems(RollbackResize, resize_rollback_resource::<T, LEN>);
+            },
+        )
+    }
 
-pub struct RollbackConfig {
-    pub systems: Vec<SystemConfigs>,
-    pub getters: Vec<Getter>,
-}
-impl RollbackConfig {
-    pub fn new() -> RollbackConfig {
-        RollbackConfig {
-            systems: Vec::new(),
-            getters: Vec::new(),
-        }
+    /// Input stored in the [`Rollback<Option<T>>`] [`Resource`] and loaded into the `T` [`Resource`] in [`RollbackUpdateSet::LoadInputs`].
+    /// The inputs are written into [`Rollback<Option<T>>`] by the user, so instead of a save system the input of every new frame
+    /// is cleared to `None` by [`clear_resource_input_option`] in [`RollbackSave`]. `T` is removed while a frame without an input is simulated.
+    pub fn register_input<T>(self) -> RollbackTypeConfig<T, InputType, LEN>
+    where
+        T: Resource + Clone + GetTypeRegistration,
+        Rollback<Option<T>, LEN>: GetTypeRegistration,
+    {
+        RollbackTypeConfig::new(self, RollbackUpdate.intern(), RollbackUpdateSet::LoadInputs.intern(), RollbackSaveSet::Resources.intern(),
+            restore_resource_option::<T, LEN>.into_configs(),
+            Some(clear_resource_input_option::<T, LEN>.into_configs()),
+            |app| {
+                app
+                .init_resource::<Rollback<Option<T>, LEN>>()
+                .register_type::<T>()
+                .register_type::<Rollback<Option<T>, LEN>>();
+                #[cfg(feature = "growable")]
+                app.add_systems(RollbackResize, resize_rollback_resource::<Option<T>, LEN>);
+            },
+        )
     }
-    pub fn apply(self, app: &mut App) {
-        for system in self.systems {
-            app.add_systems(RollbackSchedule, system);
-        }
-        app.insert_resource(RollbackRegistry {
-            getters: self.getters,
-        });
-        
-    }
-
-    pub fn register_component<T>(self) -> RollbackComponentConfig<T> {
-        RollbackComponentConfig {
-            config: self,
-            restore_system: None,
-            save_system: None,
-            getter: None,
-            _type: PhantomData,
-        }
+
+    /// Like [`register_input`](Self::register_input), but the input is stored in the [`Rollback<T>`] [`Resource`],
+    /// the input of every new frame is cleared to `T::default()` by [`clear_resource_input_default`] and `T` is inserted by [`apply`](Self::apply)
+    pub fn register_input_default<T>(self) -> RollbackTypeConfig<T, InputType, LEN>
+    where
+        T: Resource + Clone + Default + GetTypeRegistration,
+        Rollback<T, LEN>: GetTypeRegistration,
+    {
+        RollbackTypeConfig::new(self, RollbackUpdate.intern(), RollbackUpdateSet::LoadInputs.intern(), RollbackSaveSet::Resources.intern(),
+            restore_resource::<T, LEN>.into_configs(),
+            Some(clear_resource_input_default::<T, LEN>.into_configs()),
+            |app| {
+                app
+                .init_resource::<T>()
+                .init_resource::<Rollback<T, LEN>>()
+                .register_type::<T>()
+                .register_type::<Rollback<T, LEN>>();
+                #[cfg(feature = "growable")]
+                app.add_systems(RollbackResize, resize_rollback_resource::<T, LEN>);
+            },
+        )
     }
 }
 
-pub struct RollbackComponentConfig<T> {
-    config: RollbackConfig,
-    restore_system: Option<SystemConfigs>,
-    save_system: Option<SystemConfigs>,
-    getter: Option<Getter>,
-    _type: PhantomData<T>,
+/// Marker of [`RollbackTypeConfig`] created by [`RollbackConfig::register_component`]
+pub struct ComponentType;
+/// Marker of [`RollbackTypeConfig`] created by [`RollbackConfig::register_component_option`]
+pub struct OptionComponentType;
+/// Marker of [`RollbackTypeConfig`] created by [`RollbackConfig::register_resource`]
+pub struct ResourceType;
+/// Marker of [`RollbackTypeConfig`] created by [`RollbackConfig::register_input`] and [`RollbackConfig::register_input_default`]
+pub struct InputType;
+
+/// Configuration of a single type in [`RollbackConfig`], it is finished by registering the next type or by [`RollbackTypeConfig::apply`]
+pub struct RollbackTypeConfig<T, Kind, const LEN: usize> {
+    config: RollbackConfig<LEN>,
+    restore_schedule: Interned<dyn ScheduleLabel>,
+    restore_set: InternedSystemSet,
+    save_set: InternedSystemSet,
+    restore: Option<SystemConfigs>,
+    save: Option<SystemConfigs>,
+    merge: Option<SystemConfigs>,
+    checksum: Option<SystemConfigs>,
+    setup: fn(&mut App),
+    _type: std::marker::PhantomData<(T, Kind)>,
 }
-impl<T> RollbackComponentConfig<T> {
-    pub fn finish(mut self) -> RollbackConfig {
-        if let Some(getter) = self.getter {
-            self.config.getters.push(getter);
-        }else{
-            warn!("Getter function was not set for RollbackComponentConfig<{}>",std::any::type_name::<T>());
-        }
-        if let Some(restore) = self.restore_system {
-            self.config.systems.push(restore);
-        }else{
-            warn!("Restore function was not set for RollbackComponentConfig<{}>",std::any::type_name::<T>());
-        }
-        if let Some(save) = self.save_system {
-            self.config.systems.push(save);
-        }else{
-            warn!("Save function was not set for RollbackComponentConfig<{}>",std::any::type_name::<T>());
+
+impl<T: 'static, Kind: 'static, const LEN: usize> RollbackTypeConfig<T, Kind, LEN> {
+    fn new(
+        config: RollbackConfig<LEN>,
+        restore_schedule: Interned<dyn ScheduleLabel>,
+        restore_set: InternedSystemSet,
+        save_set: InternedSystemSet,
+        restore: SystemConfigs,
+        save: Option<SystemConfigs>,
+        setup: fn(&mut App),
+    ) -> Self {
+        Self {
+            config,
+            restore_schedule,
+            restore_set,
+            save_set,
+            restore: Some(restore),
+            save,
+            merge: None,
+            checksum: None,
+            setup,
+            _type: std::marker::PhantomData,
         }
-        self.config
     }
+
+    /// Replaces the default restore system
     pub fn set_restore<M>(mut self, restore_system: impl IntoSystemConfigs<M>) -> Self {
-        self.restore_system = Some(restore_system.into_configs());
+        self.restore = Some(restore_system.into_configs());
         self
     }
+
+    /// Replaces the default save system
     pub fn set_save<M>(mut self, save_system: impl IntoSystemConfigs<M>) -> Self {
-        self.save_system = Some(save_system.into_configs());
+        self.save = Some(save_system.into_configs());
+        self
+    }
+
+    /// Do not add any restore system
+    pub fn no_restore(mut self) -> Self {
+        self.restore = None;
         self
     }
-    pub fn set_getter(mut self, getter: Getter) -> Self {
-        self.getter = Some(getter);
+
+    /// Do not add any save system
+    pub fn no_save(mut self) -> Self {
+        self.save = None;
         self
     }
-    pub fn register_component<T2>(self) -> RollbackComponentConfig<T2> {
+
+    /// Sets the system which runs right after the restore system of this type.
+    /// It can merge the restored snapshot with the state which is not rolled back, for example local visual smoothing.
+    pub fn merging_policy<M>(mut self, merge_system: impl IntoSystemConfigs<M>) -> Self {
+        self.merge = Some(merge_system.into_configs());
+        self
+    }
+
+    fn finish(self) -> RollbackConfig<LEN> {
+        let Self { mut config, restore_schedule, restore_set, save_set, restore, save, merge, checksum, setup, .. } = self;
+        config.registrations.push(Box::new(move |app: &mut App| {
+            setup(app);
+            match (restore, merge) {
+                (Some(restore), Some(merge)) => {app.add_systems(restore_schedule, (restore, merge).chain().in_set(restore_set));},
+                (Some(system), None) | (None, Some(system)) => {app.add_systems(restore_schedule, system.in_set(restore_set));},
+                (None, None) => (),
+            }
+            if let Some(save) = save {
+                app.add_systems(RollbackSave, save.in_set(save_set));
+            }
+            if let Some(checksum) = checksum {
+                app.add_systems(RollbackChecksum, checksum.in_set(ChecksumSet::Hash));
+            }
+        }));
+        config
+    }
+
+    pub fn register_component<T2>(self) -> RollbackTypeConfig<T2, ComponentType, LEN>
+    where
+        T2: RollbackCapable + GetTypeRegistration,
+        (T2,): ResizableGroup,
+        Rollback<T2, LEN>: GetTypeRegistration,
+    {
         self.finish().register_component()
     }
+
+    pub fn register_component_option<T2>(self) -> RollbackTypeConfig<T2, OptionComponentType, LEN>
+    where
+        T2: RollbackCapable + GetTypeRegistration,
+        Rollback<Option<T2>, LEN>: GetTypeRegistration,
+    {
+        self.finish().register_component_option()
+    }
+
+    pub fn register_resource<T2>(self) -> RollbackTypeConfig<T2, ResourceType, LEN>
+    where
+        T2: Resource + Clone + GetTypeRegistration,
+        Rollback<T2, LEN>: GetTypeRegistration,
+    {
+        self.finish().register_resource()
+    }
+
+    pub fn register_input<T2>(self) -> RollbackTypeConfig<T2, InputType, LEN>
+    where
+        T2: Resource + Clone + GetTypeRegistration,
+        Rollback<Option<T2>, LEN>: GetTypeRegistration,
+    {
+        self.finish().register_input()
+    }
+
+    pub fn register_input_default<T2>(self) -> RollbackTypeConfig<T2, InputType, LEN>
+    where
+        T2: Resource + Clone + Default + GetTypeRegistration,
+        Rollback<T2, LEN>: GetTypeRegistration,
+    {
+        self.finish().register_input_default()
+    }
+
+    pub fn apply(self, app: &mut App) {
+        self.finish().apply(app);
+    }
 }
-impl<T: 'static + Send + Sync + Clone + Default> RollbackComponentConfig<T> { //TODO: remove Default requirement
-    pub fn default_getter(mut self) -> Self {
-        self.getter = Some(getter::<T>);
+
+impl<T: RollbackCapable, const LEN: usize> RollbackTypeConfig<T, ComponentType, LEN> {
+    /// Use the default restore and save systems only for entities matching `Filter`, this replaces the restore and save systems set before
+    pub fn filter<Filter: QueryFilter + 'static>(mut self) -> Self {
+        self.restore = Some(restore_filter::<T, LEN, Filter>.into_configs());
+        self.save = Some(save_filter::<T, LEN, Filter>.into_configs());
         self
     }
 }
-impl<T: Component + Clone + Default> RollbackComponentConfig<T> { //TODO: remove Default requirement
-    pub fn default_systems(mut self) -> Self {
-        self.restore_system = Some(restore::<T>.in_set(RollbackSet::Restore));
-        self.save_system = Some(save::<T>.in_set(RollbackSet::Save));
+
+impl<T: RollbackCapable, const LEN: usize> RollbackTypeConfig<T, OptionComponentType, LEN> {
+    /// Use the default restore and save systems only for entities matching `Filter`, this replaces the restore and save systems set before
+    pub fn filter<Filter: QueryFilter + 'static>(mut self) -> Self {
+        self.restore = Some(restore_option_filter::<T, LEN, Filter>.into_configs());
+        self.save = Some(save_option_filter::<T, LEN, Filter>.into_configs());
         self
     }
 }
-impl<T: Component + Send + Sync + Clone + Default> RollbackComponentConfig<T> { //TODO: remove Default requirement
-    pub fn defaults(self) -> Self {
-        self.default_systems().default_getter()
+
+impl<T: RollbackCapable + PartialReflect + TypePath, const LEN: usize> RollbackTypeConfig<T, ComponentType, LEN> {
+    /// Add the saved snapshots of this component into the [`Checksum`], requires the [`ChecksumPlugin`]
+    pub fn checksum(mut self) -> Self {
+        self.checksum = Some(checksum_component::<T, LEN>.into_configs());
+        self
     }
 }
 
-*/
+impl<T: RollbackCapable, const LEN: usize> RollbackTypeConfig<T, OptionComponentType, LEN> where Option<T>: PartialReflect + TypePath {
+    /// Add the saved snapshots of this component into the [`Checksum`], requires the [`ChecksumPlugin`]
+    pub fn checksum(mut self) -> Self {
+        self.checksum = Some(checksum_component::<Option<T>, LEN>.into_configs());
+        self
+    }
+}
 
-/*
-this will be used in Plugin configuration
-
-RollbackConfig::new()
-    .register_component::<Transform>()
-        .set_restore(my_transform_restore_system)
-        .set_save(my_transform_save_system)
-        .set_getter(my_getter)  //is this a good idea?
-        .configure_something(...)
-        .merging_policy(my_transform_merge_system)
-    .register_resource::<PlayerScore>()
-        .configure_something(...)
-    .register_input::<MyInput>()
-        .configure_something(...)
-        .set_restore(my_input_restore_system)
-
-
-OR
-
-RollbackConfig::new(vec![
-    register_component::<Transform>()
-        .set_restore(my_transform_restore_system)
-        .set_save(my_transform_save_system)
-        .configure_something(...),
-    register_resource::<PlayerScore>()
-        .configure_something(...),
-    register_input::<MyInput>()
-        .configure_something(...)
-        .set_restore(my_input_restore_system),
-])
-*/
\ No newline at end of file
+impl<T: Resource + PartialReflect + TypePath, const LEN: usize> RollbackTypeConfig<T, ResourceType, LEN> {
+    /// Add the saved snapshots of this resource into the [`Checksum`], requires the [`ChecksumPlugin`]
+    pub fn checksum(mut self) -> Self {
+        self.checksum = Some(checksum_resource::<T, LEN>.into_configs());
+        self
+    }
+}
+
+impl<T: Component + Clone + PartialEq, const LEN: usize> RollbackTypeConfig<T, ComponentType, LEN> {
+    /// Use [`restore_if_neq`] as the restore system, the component is written only when the restored value differs.
+    /// This replaces the restore system set before.
+    pub fn restore_if_neq(mut self) -> Self {
+        self.restore = Some(restore_if_neq::<T, LEN>.into_configs());
+        self
+    }
+}
+
+impl<T: Component + Clone, const LEN: usize> RollbackTypeConfig<T, ComponentType, LEN> {
+    /// Use [`restore_dirty`] and [`save_dirty`], only the entities which changed since the restored frame are restored.
+    /// This replaces the restore and save systems set before.
+    pub fn dirty_tracking(mut self) -> Self {
+        self.restore = Some(restore_dirty::<T, LEN>.into_configs());
+        self.save = Some(save_dirty::<T, LEN>.into_configs());
+        self
+    }
+}
+
+impl<T: Component + Clone + PartialEq, const LEN: usize> RollbackTypeConfig<T, OptionComponentType, LEN> {
+    /// Use [`restore_option_if_neq`] as the restore system, the component is written only when the restored value differs.
+    /// This replaces the restore system set before.
+    pub fn restore_if_neq(mut self) -> Self {
+        self.restore = Some(restore_option_if_neq::<T, LEN>.into_configs());
+        self
+    }
+}
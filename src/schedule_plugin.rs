@@ -1,14 +1,34 @@
 use bevy::prelude::*;
 use bevy::ecs::schedule::ScheduleLabel;
 use bevy::ecs::intern::Interned;
+use bevy::ecs::component::Tick;
+use bevy::ecs::system::{SystemChangeTick, SystemParam};
+
+use std::time::Duration;
 
 use crate::*;
 
 /// Restore the state of rollback entities that are needed to be restored. Restore Resources.
 /// For restoring inputs use [`RollbackUpdateSet::LoadInputs`] inside the [`RollbackUpdate`] [`Schedule`].
+/// The systems are ordered by [`RollbackRestoreSet`].
 #[derive(ScheduleLabel, Hash, Debug, PartialEq, Eq, Clone)]
 pub struct RollbackRestore;
 
+/// This [`SystemSet`] normally runs in [`RollbackRestore`] [`Schedule`], the sets run in the order of the variants
+#[derive(SystemSet, Hash, Debug, PartialEq, Eq, Clone)]
+pub enum RollbackRestoreSet {
+    /// Runs before anything is restored
+    PreRestore,
+    /// Despawn the entities which did not exist in the restored [`Frame`], see [`ExistencePlugin`](crate::existence_plugin::ExistencePlugin)
+    Despawn,
+    /// Restore the components of rollback entities
+    Components,
+    /// Restore Resources
+    Resources,
+    /// Runs after everything was restored. Recompute state derived from the restored components and Resources here.
+    PostRestore,
+}
+
 /// Run the game update step, use [`RollbackUpdateSet`] to put game logic into [`RollbackUpdateSet::Update`].
 #[derive(ScheduleLabel, Hash, Debug, PartialEq, Eq, Clone)]
 pub struct RollbackUpdate;
@@ -35,12 +55,138 @@ pub enum RollbackUpdateSet {
 #[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct WantedFrame(pub u64);
 
+/// Measurements of the last execution of the rollback systems, published by
+/// [`RollbackDiagnosticsPlugin`](crate::diagnostics_plugin::RollbackDiagnosticsPlugin)
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct RollbackStats {
+    /// How many frames back the restore went, `0` when nothing was restored
+    pub depth: u64,
+    /// How many frames were simulated again after the restore
+    pub resimulated_frames: u32,
+    /// Time spent in the [`RollbackRestore`] [`Schedule`]
+    pub restore_time: Duration,
+    /// Time spent in the [`RollbackUpdate`] [`Schedule`]
+    pub update_time: Duration,
+    /// Time spent in the [`RollbackSave`] [`Schedule`]
+    pub save_time: Duration,
+}
+
+/// What [`rollback_update_system`] does when [`RollbackUpdateConfig::max_update_duration`] is exceeded
+#[derive(Reflect, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UpdateBudgetStrategy {
+    /// Finish the resimulation up to [`LastFrame`], but defer the new frames to the next execution
+    #[default]
+    FinishRollback,
+    /// Stop immediately, the rest of the resimulation and the new frames are spread over the next executions
+    Spread,
+    /// Continue up to [`WantedFrame`] with [`IsReducedFidelity`] set, the systems that are not needed for the
+    /// deterministic state (for example visual only) can be skipped with the [`is_full_fidelity`] run condition
+    ReducedFidelity,
+}
+
+/// Sent by [`rollback_update_system`] when it took longer than [`RollbackUpdateConfig::max_update_duration`]
+#[derive(Event, Clone, Copy, Debug)]
+pub struct UpdateOverrun {
+    pub elapsed: Duration,
+    pub budget: Duration,
+    /// The [`Frame`] reached by the execution
+    pub frame: u64,
+    pub wanted_frame: u64,
+}
+
+/// Whether [`rollback_update_system`] ran out of its time budget and uses [`UpdateBudgetStrategy::ReducedFidelity`].
+/// It is only meaningful inside [`RollbackUpdate`], outside of it it is `false`.
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Resource)]
+pub struct IsReducedFidelity(pub bool);
+
+/// Run condition, `true` while [`RollbackUpdate`] runs with reduced fidelity
+pub fn is_reduced_fidelity(reduced: Res<IsReducedFidelity>) -> bool {
+    reduced.0
+}
+
+/// Run condition, `true` while [`RollbackUpdate`] runs with full fidelity
+pub fn is_full_fidelity(reduced: Res<IsReducedFidelity>) -> bool {
+    !reduced.0
+}
+
+/// Whether the [`RollbackUpdate`] [`Schedule`] currently simulates a frame which was already simulated before (after a rollback).
+/// It is only meaningful inside [`RollbackUpdate`], outside of it it is `false`.
+/// Use the [`is_resimulating`] and [`is_first_simulation`] run conditions to skip side effects during resimulation,
+/// or [`RollbackEffectPlugin`](crate::effect_plugin::RollbackEffectPlugin) to also cancel effects that did not happen after the rollback.
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Resource)]
+pub struct IsResimulating(pub bool);
+
+/// Run condition, `true` while [`RollbackUpdate`] resimulates a frame
+pub fn is_resimulating(resimulating: Res<IsResimulating>) -> bool {
+    resimulating.0
+}
+
+/// Run condition, `true` while [`RollbackUpdate`] simulates a frame for the first time
+pub fn is_first_simulation(resimulating: Res<IsResimulating>) -> bool {
+    !resimulating.0
+}
+
+/// The change ticks of the start and the end of the last run of the [`RollbackRestore`] [`Schedule`],
+/// the changes made by the restore have ticks newer than `start` and not newer than `end`.
+/// Use [`RestoreChanges`] to find out whether a change was made by the restore or by the simulation.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct RestoreTicks {
+    pub start: Tick,
+    pub end: Tick,
+}
+
+/// The [`Frame`] whose state the world held right before the last run of [`RollbackRestore`].
+/// It is older than the restored frame only when a converged resimulation jumps to [`LastFrame`].
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct RestoredFrom(pub u64);
+
+/// Tells apart the changes made by the last restore from the changes made by the simulation (or anything else)
+#[derive(SystemParam)]
+pub struct RestoreChanges<'w> {
+    ticks: Res<'w, RestoreTicks>,
+    system_ticks: SystemChangeTick,
+}
+
+impl RestoreChanges<'_> {
+    /// Whether `value` was last changed by the last run of [`RollbackRestore`], use it with [`Ref`] or [`Mut`]
+    pub fn changed_by_restore(&self, value: &impl DetectChanges) -> bool {
+        let this_run = self.system_ticks.this_run();
+        let tick = value.last_changed();
+        tick.is_newer_than(self.ticks.start, this_run) && !tick.is_newer_than(self.ticks.end, this_run)
+    }
+
+    /// Whether `value` was changed since the last run of this system, but not by the restore
+    pub fn changed_by_simulation(&self, value: &impl DetectChanges) -> bool {
+        value.is_changed() && !self.changed_by_restore(value)
+    }
+}
+
+/// Resize all [`Rollback`] storages to [`HistoryLength`]. This [`Schedule`] is run at the start of every [`rollback_restore_system`].
+#[cfg(feature = "growable")]
+#[derive(ScheduleLabel, Hash, Debug, PartialEq, Eq, Clone)]
+pub struct RollbackResize;
+
 /// Save the state of rollback entities and Resources (not inputs).
 /// The frame that is being saved is in [`Frame`] [`Resource`].
-/// 
+/// The systems are ordered by [`RollbackSaveSet`].
 #[derive(ScheduleLabel, Hash, Debug, PartialEq, Eq, Clone)]
 pub struct RollbackSave;
 
+/// This [`SystemSet`] normally runs in [`RollbackSave`] [`Schedule`], the sets run in the order of the variants
+#[derive(SystemSet, Hash, Debug, PartialEq, Eq, Clone)]
+pub enum RollbackSaveSet {
+    /// Runs before anything is saved, the [`Frame`] is registered by [`new_frame_save_system`] here
+    PreSave,
+    /// Save the components of rollback entities
+    Components,
+    /// Save Resources and clear the inputs of new frames
+    Resources,
+    /// Runs after everything was saved, entities which do not exist in any saved frame are despawned here
+    PostSave,
+}
+
 
 /// This [`SystemSet`] specifies the high level rollback steps. Those are:
 /// 1. Getting all the Inputs (current or old/delayed) from all "players"
@@ -61,6 +207,16 @@ pub struct RollbackUpdateConfig {
     /// How many consecutive updates are allowed inside single execution of [`rollback_update_system`].
     /// Value `0` means infinite.
     pub max_update_loops: u32,
+    /// How much time a single execution of [`rollback_update_system`] is allowed to take, `None` means infinite.
+    /// What happens when it is exceeded is set by `budget_strategy`, every overrun is reported by [`UpdateOverrun`].
+    pub max_update_duration: Option<Duration>,
+    pub budget_strategy: UpdateBudgetStrategy,
+    /// Stop the resimulation once a resimulated frame has the same [`Checksum`](checksum_plugin::Checksum) as in its previous simulation
+    /// and no later frame is [`Modified`], the stored state of [`LastFrame`] is restored instead of resimulating the remaining frames.
+    /// It requires the [`ChecksumPlugin`](checksum_plugin::ChecksumPlugin) and all the rolled back state has to be added to the checksum.
+    /// Inputs are not in the checksum, a changed input has to mark every frame whose simulation it influences as [`Modified`],
+    /// [`InputWriter`](input_plugin::InputWriter) also predicts the following inputs again and marks the frames whose prediction changed.
+    pub skip_converged: bool,
 }
 
 pub struct RollbackSchedulePlugin<const LEN: usize> {
@@ -89,18 +245,39 @@ impl<const LEN: usize> Plugin for RollbackSchedulePlugin<LEN> {
             .add_systems(schedule,(
                 rollback_restore_system::<LEN>,
                 rollback_update_system::<LEN>,
+                finalize_frames_system::<LEN>,
+                check_confirmed_frames_system::<LEN>,
             ).chain().in_set(RollbackProcessSet::RunRollbackSchedule));
         }
 
         app
         .init_resource::<WantedFrame>()
         .init_resource::<RollbackUpdateConfig>()
+        .init_resource::<IsResimulating>()
+        .init_resource::<IsReducedFidelity>()
+        .init_resource::<RollbackStats>()
+        .add_event::<UpdateOverrun>()
+        .init_resource::<RestoreTicks>()
+        .init_resource::<RestoredFrom>()
         .init_schedule(RollbackRestore)
         .init_schedule(RollbackUpdate)
         .init_schedule(RollbackSave)
+        .configure_sets(RollbackRestore, (
+            RollbackRestoreSet::PreRestore,
+            RollbackRestoreSet::Despawn,
+            RollbackRestoreSet::Components,
+            RollbackRestoreSet::Resources,
+            RollbackRestoreSet::PostRestore,
+        ).chain())
         .configure_sets(RollbackUpdate, (RollbackUpdateSet::LoadInputs, RollbackUpdateSet::Update).chain())
+        .configure_sets(RollbackSave, (
+            RollbackSaveSet::PreSave,
+            RollbackSaveSet::Components,
+            RollbackSaveSet::Resources,
+            RollbackSaveSet::PostSave,
+        ).chain())
 
-        .add_systems(RollbackSave, new_frame_save_system::<LEN>)
+        .add_systems(RollbackSave, new_frame_save_system::<LEN>.in_set(RollbackSaveSet::PreSave))
 
         //.edit_schedule(RollbackUpdate, |schedule| {
         //    schedule.set_build_settings(bevy::ecs::schedule::ScheduleBuildSettings {
@@ -109,59 +286,151 @@ impl<const LEN: usize> Plugin for RollbackSchedulePlugin<LEN> {
         //    });
         //})
         ;
+
+        #[cfg(feature = "growable")]
+        app
+        .init_schedule(RollbackResize)
+        .add_systems(RollbackResize, (
+            systems::resize_rollback_resource::<Frame, LEN>,
+            systems::resize_rollback_resource::<Modified, LEN>,
+        ));
     }
 }
 
 pub fn rollback_restore_system<const LEN: usize>(world: &mut World) {
+    #[cfg(feature = "growable")]
+    {
+        if world.resource::<HistoryLength>().0 == 0 {
+            let len = world.resource::<Rollback<Frame, LEN>>().len();
+            error!("HistoryLength(0) is not valid, keeping the length {len}");
+            world.resource_mut::<HistoryLength>().0 = len;
+        }
+        world.run_schedule(RollbackResize);
+    }
+
+    *world.resource_mut::<RollbackStats>() = RollbackStats::default();
+
     let current_frame = world.resource::<Frame>().0;
     let last_frame = world.resource::<LastFrame>().0;
+    if last_frame == 0 && world.resource::<Rollback<Frame, LEN>>().get(0).is_none() {
+        //the initial state is saved as frame 0, so that inputs and corrections of frame 0 can be applied
+        rollback_save_system::<LEN>(world);
+        return
+    }
     let modified = world.resource::<Rollback<Modified, LEN>>();
     let frames = world.resource::<Rollback<Frame, LEN>>();
 
-    let oldest_frame = last_frame.saturating_sub(LEN as u64 - 1);
+    let oldest_frame = last_frame.saturating_sub(frames.len() as u64 - 1);
 
     assert!(current_frame <= last_frame, "perhaps rollback_save_system was not run immediately after rollback_update_system");
-    for frame in oldest_frame..current_frame { //exclude the current frame, we do not need to restore the current frame as it is already loaded
-        let index = index::<LEN>(frame);
-        if modified[index].0 {
-            assert!(frames[index].0 == frame);   //TODO: this should never be possible to fail
+    //the current frame is already loaded, it is restored only when it was modified (for example by a state correction)
+    for frame in oldest_frame..=current_frame {
+        if modified.get(frame).is_some_and(|x| x.0) {
+            assert!(frames.get(frame).is_some_and(|x| x.0 == frame));   //TODO: this should never be possible to fail
             
+            if frame == current_frame {
+                //there is nothing to resimulate, the modification is resolved by the restore
+                world.resource_mut::<Rollback<Modified, LEN>>().set(frame, Modified(false));
+            }
+
             //restore this (past) frame
-            world.resource_mut::<Frame>().0 = frame;
             //world.resource_mut::<Index<LEN>>().0 = index; //maybe in the future as an optimization, now I want simplicity
-            world.run_schedule(RollbackRestore);
+            let _span = rollback_span!(debug_span, "rollback", frame, depth = current_frame - frame);
+            restore_frame(world, frame);
+            world.resource_mut::<RollbackStats>().depth = current_frame - frame;
             return
         }
     }
 }
 
+/// Sets [`Frame`] to `frame` and runs the [`RollbackRestore`] [`Schedule`]
+fn restore_frame(world: &mut World, frame: u64) {
+    let _span = rollback_span!(debug_span, "restore", frame);
+    let from = std::mem::replace(&mut world.resource_mut::<Frame>().0, frame);
+    world.resource_mut::<RestoredFrom>().0 = from;
+    //the changes made by the restore have ticks newer than start and not newer than end
+    let start = world.increment_change_tick();
+    let time = std::time::Instant::now();
+    world.run_schedule(RollbackRestore);
+    world.resource_mut::<RollbackStats>().restore_time += time.elapsed();
+    //the commands flushed at the end of the schedule have the end tick, the systems which run later get newer ticks
+    let end = world.increment_change_tick();
+    *world.resource_mut::<RestoreTicks>() = RestoreTicks { start, end };
+}
+
+/// Whether the resimulation converged in `frame` (which was just saved), see [`RollbackUpdateConfig::skip_converged`]
+fn is_converged<const LEN: usize>(world: &World, frame: u64) -> bool {
+    let last_frame = world.resource::<LastFrame>().0;
+    if frame >= last_frame || !world.get_resource::<checksum_plugin::ChecksumConverged>().is_some_and(|x| x.0) {
+        return false
+    }
+    let modified = world.resource::<Rollback<Modified, LEN>>();
+    !(frame..=last_frame).any(|x| modified.get(x).is_some_and(|x| x.0))
+}
+
 pub fn rollback_update_system<const LEN: usize>(world: &mut World) {
     let time = std::time::Instant::now();
 
     let mut current_frame = world.resource::<Frame>().0;
     let wanted_frame = world.resource::<WantedFrame>().0;
 
+    let _span = rollback_span!(debug_span, "update", frame = current_frame, wanted_frame);
+
     let rollback_update_config = world.resource::<RollbackUpdateConfig>().clone();
     let mut i = 0u32;
     loop {
         if wanted_frame > current_frame {
             //run the update to move to the next frame
-            let current_index = index::<LEN>(current_frame);
             let mut modified = world.resource_mut::<Rollback<Modified, LEN>>();
-            modified[current_index].0 = false;  //if this frame was modified, by resimulating it, we resolved any changes that could be there
+            if let Some(modified) = modified.get_mut(current_frame) {
+                modified.0 = false;  //if this frame was modified, by resimulating it, we resolved any changes that could be there
+            }
             
+            let last_frame = world.resource::<LastFrame>().0;
+            let _frame_span = rollback_span!(trace_span, "frame", frame = current_frame, resimulating = current_frame < last_frame);
+            world.resource_mut::<IsResimulating>().0 = current_frame < last_frame;
+            let update_time = std::time::Instant::now();
             world.run_schedule(RollbackUpdate);
+            let mut stats = world.resource_mut::<RollbackStats>();
+            stats.update_time += update_time.elapsed();
+            if current_frame < last_frame {
+                stats.resimulated_frames += 1;
+            }
             
             current_frame += 1;     //now we are in the next frame, LastFrame will be updated accordingly in rollback_save_system in case Frame > LastFrame
             world.resource_mut::<Frame>().0 = current_frame;
 
             rollback_save_system::<LEN>(world);
+
+            if world.resource::<Rollback<Modified, LEN>>().get(current_frame).is_some_and(|x| x.0) {
+                //the frame was corrected before this resimulation reached it, the corrected snapshot replaces the simulated state
+                restore_frame(world, current_frame);
+            }
+
+            if rollback_update_config.skip_converged && is_converged::<LEN>(world, current_frame) {
+                //the following frames would be simulated the same as before, their snapshots are still valid
+                current_frame = world.resource::<LastFrame>().0;
+                rollback_debug!(frame = world.resource::<Frame>().0, "Resimulation converged, skipping to frame {current_frame}");
+                restore_frame(world, current_frame);
+            }
         }else{
             break //update is not wanted
         }
 
         i += 1;
 
+        if let Some(budget) = rollback_update_config.max_update_duration {
+            if time.elapsed() > budget {
+                match rollback_update_config.budget_strategy {
+                    UpdateBudgetStrategy::FinishRollback => if current_frame >= world.resource::<LastFrame>().0 {
+                        break   //the rollback is finished, the new frames are deferred
+                    },
+                    UpdateBudgetStrategy::Spread => break,
+                    UpdateBudgetStrategy::ReducedFidelity => world.resource_mut::<IsReducedFidelity>().0 = true,
+                }
+            }
+        }
+
         if rollback_update_config.max_update_loops == 0 {
             continue    //no limit is set
         }
@@ -170,11 +439,12 @@ pub fn rollback_update_system<const LEN: usize>(world: &mut World) {
             break   //the maximum number of allowed updates happened
         }
     }
+    world.resource_mut::<IsResimulating>().0 = false;
+    world.resource_mut::<IsReducedFidelity>().0 = false;
 
-    //TODO: debugging should be configurable in rollback_update_config
-    let elapsed = time.elapsed().as_secs_f32();
-    if elapsed > 0.01 {
-        println!("run_rollback_schedule_system elapsed {elapsed}");
+    let elapsed = time.elapsed();
+    if let Some(budget) = rollback_update_config.max_update_duration.filter(|x| elapsed > *x) {
+        world.send_event(UpdateOverrun { elapsed, budget, frame: current_frame, wanted_frame });
     }
 }
 
@@ -182,7 +452,12 @@ pub fn rollback_update_system<const LEN: usize>(world: &mut World) {
 /// instead of running in a [`Schedule`].
 pub fn rollback_save_system<const LEN: usize>(world: &mut World) {
     //the RollbackSave Schedule is being run first so that the systems inside it can detect when current_frame > last_frame, if they need it
+    let _span = rollback_span!(trace_span, "save", frame = world.resource::<Frame>().0);
+    let time = std::time::Instant::now();
     world.run_schedule(RollbackSave);
+    world.resource_mut::<RollbackStats>().save_time += time.elapsed();
+    //the checksum is optional, the schedule exists only when the ChecksumPlugin was added
+    let _ = world.try_run_schedule(checksum_plugin::RollbackChecksum);
 
     let current_frame = world.resource::<Frame>().0;
     let last_frame = &mut world.resource_mut::<LastFrame>().0;
@@ -193,16 +468,72 @@ pub fn rollback_save_system<const LEN: usize>(world: &mut World) {
     }
 }
 
+/// Updates [`ConfirmedFrame`] from [`InputSources`] and sends [`FrameFinalized`] for every frame
+/// which was simulated with confirmed inputs since the last run
+pub fn finalize_frames_system<const LEN: usize>(
+    sources: Res<InputSources>,
+    current_frame: Res<Frame>,
+    modified: Res<Rollback<Modified, LEN>>,
+    mut confirmed: ResMut<ConfirmedFrame>,
+    mut finalized: ResMut<FinalizedFrame>,
+    mut events: EventWriter<FrameFinalized>,
+) {
+    let confirmed_frame = sources.confirmed_frame();
+    if confirmed.0 != confirmed_frame {
+        confirmed.0 = confirmed_frame;
+    }
+
+    let Some(confirmed_frame) = confirmed_frame else {return};
+    if current_frame.0 == 0 {
+        return
+    }
+    //the update of frame F is done when Frame > F, the resimulation could be unfinished because of max_update_loops
+    let mut newest = confirmed_frame.min(current_frame.0 - 1);
+    let next = finalized.0.map_or(0, |x| x+1);
+    if next > newest {
+        return
+    }
+    //a modified frame still has to be resimulated
+    if let Some(frame) = (next..=newest).find(|x| modified.get(*x).is_some_and(|x| x.0)) {
+        if frame == next {
+            return
+        }
+        newest = frame - 1;
+    }
+    events.send_batch((next..=newest).map(FrameFinalized));
+    finalized.0 = Some(newest);
+}
+
+/// Reports every input source whose confirmed frame fell behind the rollback history by [`RollbackEventError`] (once per confirmed frame).
+/// The inputs of the next frame of the source can not be inserted anymore, so neither the source nor [`ConfirmedFrame`] advance again.
+pub fn check_confirmed_frames_system<const LEN: usize>(
+    sources: Res<InputSources>,
+    last_frame: Res<LastFrame>,
+    frames: Res<Rollback<Frame, LEN>>,
+    mut reported: Local<HashMap<&'static str, u64>>,
+    mut errors: EventWriter<RollbackEventError>,
+) {
+    let oldest_frame = last_frame.0.saturating_sub(frames.len() as u64 - 1);
+    for (&source, confirmed) in &sources.0 {
+        let Some(next) = confirmed.map(|x| x+1) else {continue};
+        if next >= oldest_frame || reported.get(source) == Some(&next) {
+            continue
+        }
+        warn!("Confirmed frame of {source} fell behind the rollback history, frame {next} is older than the oldest stored frame {oldest_frame}");
+        errors.send(RollbackEventError { frame: next, type_name: source, kind: RollbackEventErrorKind::TooOld { oldest_frame } });
+        reported.insert(source, next);
+    }
+}
+
 pub fn new_frame_save_system<const LEN: usize>(
     current_frame: Res<Frame>,
     last_frame: Res<LastFrame>,
     mut frames: ResMut<Rollback<Frame, LEN>>,
     mut modified: ResMut<Rollback<Modified, LEN>>
 ) {
-    if current_frame.0 > last_frame.0 {
-        let current_index = crate::index::<LEN>(current_frame.0);
-        //we are saving a new frame, by default it should have modified=false
-        frames[current_index] = *current_frame;
-        modified[current_index].0 = false;
+    if current_frame.0 > last_frame.0 || frames.get(current_frame.0).is_none() {
+        //we are saving a new frame (or the initial frame 0), by default it should have modified=false
+        frames.set(current_frame.0, *current_frame);
+        modified.set(current_frame.0, Modified(false));
     }
 }
\ No newline at end of file
@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::ecs::intern::Interned;
+use bevy::utils::HashMap;
+
+use crate::*;
+use crate::schedule_plugin::*;
+use crate::checksum_plugin::*;
+
+/// Testing mode which every tick marks the frame `check_distance` frames back as [`Modified`],
+/// which forces a rollback and resimulation of the last `check_distance` frames.
+/// The saved state of every resimulated frame is compared to the state saved when the frame was simulated for the first time,
+/// every difference is reported with a [`SyncTestMismatch`] [`Event`].
+///
+/// Adds the [`ChecksumPlugin`] if it was not added yet.
+pub struct SyncTestPlugin<const LEN: usize> {
+    /// How many frames back should be resimulated every tick, it should be less than the history length
+    pub check_distance: u64,
+    /// The [`Schedule`] in which the rollback is forced, it should be the same as in [`RollbackSchedulePlugin`]
+    pub rollback_processing_schedule: Interned<dyn ScheduleLabel>,
+}
+
+impl<const LEN: usize> Default for SyncTestPlugin<LEN> {
+    fn default() -> Self {
+        Self {
+            check_distance: 2,
+            rollback_processing_schedule: Update.intern(),
+        }
+    }
+}
+
+impl<const LEN: usize> Plugin for SyncTestPlugin<LEN> {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<ChecksumPlugin<LEN>>() {
+            app.add_plugins(ChecksumPlugin::<LEN>);
+        }
+
+        app
+        .insert_resource(SyncTestConfig { check_distance: self.check_distance })
+        .init_resource::<SyncTestHistory>()
+        .init_resource::<ChecksumDetails>()
+        .add_event::<SyncTestMismatch>()
+        .add_systems(self.rollback_processing_schedule, sync_test_force_rollback_system::<LEN>
+            .after(RollbackProcessSet::HandleIO)
+            .before(RollbackProcessSet::RunRollbackSchedule)
+        )
+        .add_systems(RollbackChecksum, sync_test_compare_system::<LEN>.after(ChecksumSet::Store));
+    }
+}
+
+#[derive(Resource, Reflect, Clone, Copy, Debug)]
+#[reflect(Resource)]
+pub struct SyncTestConfig {
+    /// How many frames back should be resimulated every tick
+    pub check_distance: u64,
+}
+
+/// Sent when the state saved during a resimulation differs from the state saved during the first simulation of the frame
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SyncTestMismatch {
+    pub frame: u64,
+    pub type_name: &'static str,
+    /// `None` for resources
+    pub id: Option<RollbackID>,
+    /// The checksum from the first simulation, `None` if the state did not exist then
+    pub expected: Option<u64>,
+    /// The checksum from the resimulation, `None` if the state does not exist now
+    pub found: Option<u64>,
+}
+
+/// The checksums of every type and [`RollbackID`] saved during the first simulation of each frame
+#[derive(Resource, Default)]
+pub struct SyncTestHistory(pub HashMap<u64, HashMap<(&'static str, Option<RollbackID>), u64>>);
+
+pub fn sync_test_force_rollback_system<const LEN: usize>(
+    config: Res<SyncTestConfig>,
+    last_frame: Res<LastFrame>,
+    frames: Res<Rollback<Frame, LEN>>,
+    mut modified: ResMut<Rollback<Modified, LEN>>,
+) {
+    if config.check_distance == 0 || last_frame.0 < config.check_distance {
+        return
+    }
+    let frame = last_frame.0 - config.check_distance;
+    if frames.get(frame).is_some_and(|x| x.0 == frame) {
+        if let Some(modified) = modified.get_mut(frame) {
+            modified.0 = true;
+        }
+    }
+}
+
+pub fn sync_test_compare_system<const LEN: usize>(
+    current_frame: Res<Frame>,
+    last_frame: Res<LastFrame>,
+    frames: Res<Rollback<Frame, LEN>>,
+    details: Res<ChecksumDetails>,
+    mut history: ResMut<SyncTestHistory>,
+    mut mismatches: EventWriter<SyncTestMismatch>,
+) {
+    let frame = current_frame.0;
+    let current = details.0.iter().map(|x| ((x.type_name, x.id), x.checksum)).collect::<HashMap<_, _>>();
+
+    //LastFrame is updated after the RollbackChecksum Schedule, so a frame newer than LastFrame is simulated for the first time
+    if frame > last_frame.0 {
+        let oldest_frame = frame.saturating_sub(frames.len() as u64 - 1);
+        history.0.retain(|x, _| *x >= oldest_frame);
+        history.0.insert(frame, current);
+        return
+    }
+
+    let Some(expected) = history.0.get(&frame) else {return};
+    let mut report = |(type_name, id): (&'static str, Option<RollbackID>), expected: Option<u64>, found: Option<u64>| {
+        error!("SyncTest mismatch in frame {frame}: {type_name} of {id:?}, expected checksum {expected:?} found {found:?}");
+        mismatches.send(SyncTestMismatch { frame, type_name, id, expected, found });
+    };
+    for (key, checksum) in expected {
+        match current.get(key) {
+            Some(found) if found == checksum => (),
+            found => report(*key, Some(*checksum), found.copied()),
+        }
+    }
+    for (key, checksum) in &current {
+        if !expected.contains_key(key) {
+            report(*key, None, Some(*checksum));
+        }
+    }
+}
@@ -3,6 +3,11 @@ use bevy::ecs::query::{WorldQuery, QueryData, QueryFilter};
 use bevy::ecs::system::{StaticSystemParam, SystemParam};
 
 use crate::*;
+use crate::schedule_plugin::RestoredFrom;
+
+/// With the `derive` feature this can be derived for structs whose fields are snapshots of components
+#[cfg(feature = "derive")]
+pub use bevy_gravirollback_derive::RollbackCapable;
 
 pub trait RollbackCapable: Send + Sync + 'static {
     type RestoreQuery<'a>: QueryData;
@@ -48,39 +53,47 @@ impl<T: Component + Clone> RollbackCapable for T
     }
 }
 
-/*
-//negative impls
-//https://github.com/rust-lang/rust/issues/68318
-trait NotTupleHack {}
-impl<T> NotTupleHack for T {}
-macro_rules! tuple_hack_impl {
-    ($($T: ident),*) => { impl<$($T,)*> !NotTupleHack for ($($T,)*) {} }
-}
-macro_rules! tuple_impl {
-    ($(($T: ident, $t: ident, $s: ident)),*) => {
-        impl<$($T: Component + Clone),*> RollbackCapable3 for ($($T,)*) {
-            type QueryItem = ();
+//a blanket impl for tuples would overlap with the impl for T: Component + Clone (it needs negative impls, https://github.com/rust-lang/rust/issues/68318)
+//so the tuples are wrapped in the local Group type, which is never a Component
+
+/// Snapshot of a tuple of components as one unit: one [`Rollback<Group<(A, B, ...)>>`] ring buffer per entity
+/// and one query per restore and save, instead of one for every component.
+/// With [`Rollback<Option<Group<...>>>`] the whole group is saved only when the entity has all of the components,
+/// and it is inserted or removed as a whole on restore.
+/// For a [`Bundle`] struct use `#[derive(RollbackCapable)]` (the `derive` feature) instead.
+#[derive(Reflect, Default, Clone, Debug, Deref, DerefMut)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Group<T>(pub T);
+
+macro_rules! impl_group {
+    ($(($T:ident, $t:ident, $s:ident)),*) => {
+        impl<$($T: Component + Clone),*> RollbackCapable for Group<($($T,)*)> {
             type RestoreQuery<'a> = ($(&'a mut $T,)*);
+            type RestoreExtraParam<'a> = ();
+            type SaveQuery<'a> = ($(&'a $T,)*);
+            type SaveExtraParam<'a> = ();
 
-            fn restore(&self, ($(mut $t,)*): ($(Mut<'_, $T>,)*)) {
-                let ($($s,)*) = self;
+            fn restore(&self, ($(mut $t,)*): ($(Mut<$T>,)*), _extra: &mut StaticSystemParam<()>) {
+                let ($($s,)*) = &self.0;
                 $(*$t = $s.clone();)*
             }
 
-            fn insert(&self, entity: Entity, mut commands: Commands) {
-                commands.entity(entity).insert(self.clone());
+            fn save(($($t,)*): ($(&$T,)*), _extra: &mut StaticSystemParam<()>) -> Self {
+                Group(($($t.clone(),)*))
+            }
+
+            fn insert(&self, entity: Entity, commands: &mut Commands) {
+                commands.entity(entity).insert(self.0.clone());
             }
-        
-            fn remove(entity: Entity, mut commands: Commands) {
-                commands.entity(entity).remove::<Self>();
+
+            fn remove(entity: Entity, commands: &mut Commands) {
+                commands.entity(entity).remove::<($($T,)*)>();
             }
         }
-    }
+    };
 }
-use bevy_utils::all_tuples;
-all_tuples!(tuple_hack_impl, 1, 15, T);
-all_tuples!(tuple_impl, 1, 15, T, t, s);
-*/
+
+bevy_utils::all_tuples!(impl_group, 1, 15, T, t, s);
 
 //so that the user can pick Rollback<Option<T>> or Rollback<T>
 /*impl<T: RollbackCapable3> RollbackCapable3 for Option<T> {
@@ -102,23 +115,43 @@ all_tuples!(tuple_impl, 1, 15, T, t, s);
 
 pub(crate) type DefaultFilter = ();    //With<RollbackID>;
 
-//TODO: allow using Bundles, tuples, etc... for T, example: Rollback<(Transform, Velocity)>
+/// Used by the restore systems to report a missing snapshot with a [`MissingSnapshot`] [`Event`]
+/// and to get the [`MissingSnapshotPolicy`] that should be applied
+#[derive(SystemParam)]
+pub struct MissingSnapshots<'w> {
+    policy: Res<'w, MissingSnapshotPolicy>,
+    events: EventWriter<'w, MissingSnapshot>,
+}
+
+impl MissingSnapshots<'_> {
+    pub fn report<T>(&mut self, entity: Option<Entity>, frame: u64) -> MissingSnapshotPolicy {
+        let type_name = std::any::type_name::<T>();
+        rollback_debug!(frame, ?entity, "Missing snapshot of {type_name}");
+        self.events.send(MissingSnapshot { entity, frame, type_name });
+        *self.policy
+    }
+}
+
+//tuples of components can be rolled back as one unit with Group, for example Rollback<Group<(Transform, Velocity)>>
 //the default restore and save rollback systems, the user can use their own
 pub fn restore<T: RollbackCapable, const LEN: usize>(
     current_frame: Res<Frame>,
-    query: Query<(T::RestoreQuery<'_>, &Rollback<T, LEN>), DefaultFilter>,
+    query: Query<(Entity, T::RestoreQuery<'_>, &Rollback<T, LEN>), DefaultFilter>,
     extra: StaticSystemParam<T::RestoreExtraParam<'_>>,
+    missing: MissingSnapshots,
+    commands: Commands,
 ) {
-    restore_filter(current_frame, query, extra);
+    restore_filter(current_frame, query, extra, missing, commands);
 }
 
 pub fn restore_option<T: RollbackCapable, const LEN: usize>(
     current_frame: Res<Frame>,
     query: Query<(Entity, Option<T::RestoreQuery<'_>>, &Rollback<Option<T>, LEN>), DefaultFilter>,
     extra: StaticSystemParam<T::RestoreExtraParam<'_>>,
+    missing: MissingSnapshots,
     commands: Commands,
 ) {
-    restore_option_filter(current_frame, query, extra, commands);
+    restore_option_filter(current_frame, query, extra, missing, commands);
 }
 
 pub fn save<T: RollbackCapable, const LEN: usize>(
@@ -139,12 +172,20 @@ pub fn save_option<T: RollbackCapable, const LEN: usize>(
 
 pub fn restore_filter<T: RollbackCapable, const LEN: usize, Filter: QueryFilter>(
     current_frame: Res<Frame>,
-    mut query: Query<(T::RestoreQuery<'_>, &Rollback<T, LEN>), Filter>,
+    mut query: Query<(Entity, T::RestoreQuery<'_>, &Rollback<T, LEN>), Filter>,
     mut extra: StaticSystemParam<T::RestoreExtraParam<'_>>,
+    mut missing: MissingSnapshots,
+    mut commands: Commands,
 ) {
-    let current_index = crate::index::<LEN>(current_frame.0);
-    for (q, r) in &mut query {
-        r.0[current_index].restore(q, &mut extra);
+    for (e, q, r) in &mut query {
+        if let Some(to_restore) = r.get(current_frame.0) {
+            to_restore.restore(q, &mut extra);
+        }else{
+            match missing.report::<T>(Some(e), current_frame.0) {
+                MissingSnapshotPolicy::Skip | MissingSnapshotPolicy::KeepCurrent => (),
+                MissingSnapshotPolicy::Despawn => commands.entity(e).despawn_recursive(),
+            }
+        }
     }
 }
 
@@ -152,14 +193,98 @@ pub fn restore_option_filter<T: RollbackCapable, const LEN: usize, Filter: Query
     current_frame: Res<Frame>,
     mut query: Query<(Entity, Option<T::RestoreQuery<'_>>, &Rollback<Option<T>, LEN>), Filter>,
     mut extra: StaticSystemParam<T::RestoreExtraParam<'_>>,
+    mut missing: MissingSnapshots,
     mut commands: Commands,
 ) {
-    let current_index = crate::index::<LEN>(current_frame.0);
     for (e, q, r) in &mut query {
-        match (&r.0[current_index], q) {
+        let to_restore = match r.get(current_frame.0) {
+            Some(to_restore) => to_restore,
+            None => match missing.report::<T>(Some(e), current_frame.0) {
+                MissingSnapshotPolicy::Skip => &None,
+                MissingSnapshotPolicy::KeepCurrent => continue,
+                MissingSnapshotPolicy::Despawn => {
+                    commands.entity(e).despawn_recursive();
+                    continue
+                },
+            },
+        };
+        match (to_restore, q) {
             (Some(to_restore), None) => to_restore.insert(e, &mut commands),
             (Some(to_restore), Some(q)) => to_restore.restore(q, &mut extra),
-            (None, Some(_)) => T::remove(e, &mut commands),
+            //the query does not match when only a part of a Group exists, that part is removed too
+            (None, _) => T::remove(e, &mut commands),
+        }
+    }
+}
+
+//restore systems for components implementing PartialEq, a component is written (and so marked as changed) only when
+//the restored value differs from the current one, so Changed<T> does not fire for components which the rollback did not change
+
+pub fn restore_if_neq<T: Component + Clone + PartialEq, const LEN: usize>(
+    current_frame: Res<Frame>,
+    query: Query<(Entity, &mut T, &Rollback<T, LEN>), DefaultFilter>,
+    missing: MissingSnapshots,
+    commands: Commands,
+) {
+    restore_if_neq_filter(current_frame, query, missing, commands);
+}
+
+/// The [`QueryData`] of [`restore_option_if_neq`]
+pub type RestoreOptionIfNeqData<'a, T, const LEN: usize> = (Entity, Option<&'a mut T>, &'a Rollback<Option<T>, LEN>);
+
+pub fn restore_option_if_neq<T: Component + Clone + PartialEq, const LEN: usize>(
+    current_frame: Res<Frame>,
+    query: Query<RestoreOptionIfNeqData<'_, T, LEN>, DefaultFilter>,
+    missing: MissingSnapshots,
+    commands: Commands,
+) {
+    restore_option_if_neq_filter(current_frame, query, missing, commands);
+}
+
+pub fn restore_if_neq_filter<T: Component + Clone + PartialEq, const LEN: usize, Filter: QueryFilter>(
+    current_frame: Res<Frame>,
+    mut query: Query<(Entity, &mut T, &Rollback<T, LEN>), Filter>,
+    mut missing: MissingSnapshots,
+    mut commands: Commands,
+) {
+    for (e, mut q, r) in &mut query {
+        if let Some(to_restore) = r.get(current_frame.0) {
+            if *q != *to_restore {
+                *q = to_restore.clone();
+            }
+        }else{
+            match missing.report::<T>(Some(e), current_frame.0) {
+                MissingSnapshotPolicy::Skip | MissingSnapshotPolicy::KeepCurrent => (),
+                MissingSnapshotPolicy::Despawn => commands.entity(e).despawn_recursive(),
+            }
+        }
+    }
+}
+
+pub fn restore_option_if_neq_filter<T: Component + Clone + PartialEq, const LEN: usize, Filter: QueryFilter>(
+    current_frame: Res<Frame>,
+    mut query: Query<RestoreOptionIfNeqData<'_, T, LEN>, Filter>,
+    mut missing: MissingSnapshots,
+    mut commands: Commands,
+) {
+    for (e, q, r) in &mut query {
+        let to_restore = match r.get(current_frame.0) {
+            Some(to_restore) => to_restore,
+            None => match missing.report::<T>(Some(e), current_frame.0) {
+                MissingSnapshotPolicy::Skip => &None,
+                MissingSnapshotPolicy::KeepCurrent => continue,
+                MissingSnapshotPolicy::Despawn => {
+                    commands.entity(e).despawn_recursive();
+                    continue
+                },
+            },
+        };
+        match (to_restore, q) {
+            (Some(to_restore), None) => {commands.entity(e).insert(to_restore.clone());},
+            (Some(to_restore), Some(mut q)) => if *q != *to_restore {
+                *q = to_restore.clone();
+            },
+            (None, Some(_)) => {commands.entity(e).remove::<T>();},
             (None, None) => (),
         }
     }
@@ -170,9 +295,8 @@ pub fn save_filter<T: RollbackCapable, const LEN: usize, Filter: QueryFilter>(
     mut query: Query<(T::SaveQuery<'_>, &mut Rollback<T, LEN>), Filter>,
     mut extra: StaticSystemParam<T::SaveExtraParam<'_>>,
 ) {
-    let current_index = crate::index::<LEN>(current_frame.0);
     for (q, mut r) in &mut query {
-        r.0[current_index] = T::save(q, &mut extra);
+        r.set(current_frame.0, T::save(q, &mut extra));
     }
 }
 
@@ -181,32 +305,125 @@ pub fn save_option_filter<T: RollbackCapable, const LEN: usize, Filter: QueryFil
     mut query: Query<(Option<T::SaveQuery<'_>>, &mut Rollback<Option<T>, LEN>), Filter>,
     mut extra: StaticSystemParam<T::SaveExtraParam<'_>>,
 ) {
-    let current_index = crate::index::<LEN>(current_frame.0);
     for (q, mut r) in &mut query {
-        r.0[current_index] = q.map(|q| T::save(q, &mut extra));
+        r.set(current_frame.0, q.map(|q| T::save(q, &mut extra)));
+    }
+}
+
+//dirty tracking: the save system records the newest frame in which the component changed (by Bevy change detection)
+//compared to the frame before, the restore system then skips the entities which did not change since the restored frame,
+//their current value is the same as the restored snapshot
+
+/// The newest saved frame in which `T` of this entity was changed compared to the frame before.
+/// It is inserted and updated by [`save_dirty`], [`restore_dirty`] skips the entity when it did not change since the restored frame.
+#[derive(Component, Debug)]
+pub struct LastChange<T: Send + Sync + 'static> {
+    pub frame: u64,
+    _type: std::marker::PhantomData<T>,
+}
+
+impl<T: Send + Sync + 'static> LastChange<T> {
+    pub fn new(frame: u64) -> Self {
+        Self { frame, _type: std::marker::PhantomData }
+    }
+}
+
+/// The [`QueryData`] of [`restore_dirty`]
+pub type RestoreDirtyData<'a, T, const LEN: usize> = (Entity, &'a mut T, &'a Rollback<T, LEN>, Option<&'a LastChange<T>>);
+/// The [`QueryData`] of [`save_dirty`]
+pub type SaveDirtyData<'a, T, const LEN: usize> = (Entity, Ref<'a, T>, &'a mut Rollback<T, LEN>, Option<&'a mut LastChange<T>>);
+
+/// Restores only the entities whose `T` changed since the restored frame (or which have no [`LastChange<T>`] yet).
+/// Skipping is valid only when the world holds the state of a frame which is not older than the restored one,
+/// [`LastChange<T>`] knows nothing about the frames after that. A newer frame (from [`RestoredFrom`]) is always restored fully.
+pub fn restore_dirty<T: Component + Clone, const LEN: usize>(
+    current_frame: Res<Frame>,
+    restored_from: Res<RestoredFrom>,
+    query: Query<RestoreDirtyData<'_, T, LEN>, DefaultFilter>,
+    missing: MissingSnapshots,
+    commands: Commands,
+) {
+    restore_dirty_filter(current_frame, restored_from, query, missing, commands);
+}
+
+pub fn save_dirty<T: Component + Clone, const LEN: usize>(
+    current_frame: Res<Frame>,
+    query: Query<SaveDirtyData<'_, T, LEN>, DefaultFilter>,
+    commands: Commands,
+) {
+    save_dirty_filter(current_frame, query, commands);
+}
+
+pub fn restore_dirty_filter<T: Component + Clone, const LEN: usize, Filter: QueryFilter>(
+    current_frame: Res<Frame>,
+    restored_from: Res<RestoredFrom>,
+    mut query: Query<RestoreDirtyData<'_, T, LEN>, Filter>,
+    mut missing: MissingSnapshots,
+    mut commands: Commands,
+) {
+    for (e, mut q, r, last_change) in &mut query {
+        //LastChange only knows about the frames up to the one the world holds, a newer frame is always restored
+        if current_frame.0 <= restored_from.0 && last_change.is_some_and(|x| x.frame <= current_frame.0) {
+            continue    //not changed since the restored frame
+        }
+        if let Some(to_restore) = r.get(current_frame.0) {
+            *q = to_restore.clone();
+        }else{
+            match missing.report::<T>(Some(e), current_frame.0) {
+                MissingSnapshotPolicy::Skip | MissingSnapshotPolicy::KeepCurrent => (),
+                MissingSnapshotPolicy::Despawn => commands.entity(e).despawn_recursive(),
+            }
+        }
+    }
+}
+
+pub fn save_dirty_filter<T: Component + Clone, const LEN: usize, Filter: QueryFilter>(
+    current_frame: Res<Frame>,
+    mut query: Query<SaveDirtyData<'_, T, LEN>, Filter>,
+    mut commands: Commands,
+) {
+    for (e, q, mut r, last_change) in &mut query {
+        r.set(current_frame.0, q.clone());
+        match last_change {
+            //changed since the last save, which was the save of the previous frame or the restore
+            Some(mut last_change) => if q.is_changed() {
+                last_change.frame = current_frame.0;
+            },
+            None => {commands.entity(e).insert(LastChange::<T>::new(current_frame.0));},
+        }
     }
 }
 
 //make the same systems for Resources
 //the user can then use arbitrary types for storing Inputs and still have rollback work for them
 
-pub fn restore_resource<T: Resource + Clone + Default, const LEN: usize>( //TODO: remove Default requirement
+//a missing snapshot of a Resource is reported, but the current value is always kept
+
+pub fn restore_resource<T: Resource + Clone, const LEN: usize>(
     current_frame: Res<Frame>,
     rollback: Res<Rollback<T, LEN>>,
     mut resource: ResMut<T>,
+    mut missing: MissingSnapshots,
 ) {
-    let current_index = crate::index::<LEN>(current_frame.0);
-    *resource = rollback.0[current_index].clone();
+    if let Some(res) = rollback.get(current_frame.0) {
+        *resource = res.clone();
+    }else{
+        missing.report::<T>(None, current_frame.0);
+    }
 }
 
 pub fn restore_resource_option<T: Resource + Clone, const LEN: usize>(
     current_frame: Res<Frame>,
     rollback: Res<Rollback<Option<T>, LEN>>,
     resource: Option<ResMut<T>>,
+    mut missing: MissingSnapshots,
     mut commands: Commands,
 ) {
-    let current_index = crate::index::<LEN>(current_frame.0);
-    if let Some(res) = &rollback.0[current_index] {
+    let Some(res) = rollback.get(current_frame.0) else {
+        missing.report::<T>(None, current_frame.0);
+        return
+    };
+    if let Some(res) = res {
         if let Some(mut resource) = resource {
             *resource = res.clone();
         }else{
@@ -217,13 +434,12 @@ pub fn restore_resource_option<T: Resource + Clone, const LEN: usize>(
     }
 }
 
-pub fn save_resource<T: Resource + Clone + Default, const LEN: usize>( //TODO: remove Default requirement
+pub fn save_resource<T: Resource + Clone, const LEN: usize>(
     current_frame: Res<Frame>,
     mut rollback: ResMut<Rollback<T, LEN>>,
     resource: Res<T>,
 ) {
-    let current_index = crate::index::<LEN>(current_frame.0);
-    rollback.0[current_index] = resource.clone();
+    rollback.set(current_frame.0, resource.clone());
 }
 
 pub fn save_resource_option<T: Resource + Clone, const LEN: usize>(
@@ -231,10 +447,11 @@ pub fn save_resource_option<T: Resource + Clone, const LEN: usize>(
     mut rollback: ResMut<Rollback<Option<T>, LEN>>,
     resource: Option<Res<T>>,
 ) {
-    let current_index = crate::index::<LEN>(current_frame.0);
-    rollback.0[current_index] = resource.map(|x| x.clone());
+    rollback.set(current_frame.0, resource.map(|x| x.clone()));
 }
 
+/// Clears the input of every new frame to `T::default()`, inputs without a meaningful default can use
+/// [`Rollback<Option<T>>`] with [`clear_resource_input_option`] instead
 pub fn clear_resource_input_default<T: Resource + Default, const LEN: usize>(
     current_frame: Res<Frame>,
     last_frame: Res<LastFrame>,
@@ -242,8 +459,7 @@ pub fn clear_resource_input_default<T: Resource + Default, const LEN: usize>(
 ) {
     if current_frame.0 > last_frame.0 {
         //this input is completely new, so it should be cleared
-        let current_index = crate::index::<LEN>(current_frame.0);
-        rollback.0[current_index] = default();
+        rollback.set(current_frame.0, default());
     }
 }
 
@@ -254,11 +470,37 @@ pub fn clear_resource_input_option<T: Resource + Clone, const LEN: usize>(
 ) {
     if current_frame.0 > last_frame.0 {
         //this input is completely new, so it should be cleared
-        let current_index = crate::index::<LEN>(current_frame.0);
-        rollback.0[current_index] = None;
+        rollback.set(current_frame.0, None);
+    }
+}
+
+/// Resizes the [`Rollback<T>`] storages of all entities to [`HistoryLength`], used in the [`RollbackResize`](crate::schedule_plugin::RollbackResize) [`Schedule`]
+#[cfg(feature = "growable")]
+pub fn resize_rollback<T: Send + Sync + 'static, const LEN: usize>(
+    length: Res<HistoryLength>,
+    last_frame: Res<LastFrame>,
+    mut query: Query<&mut Rollback<T, LEN>>,
+) {
+    for mut r in &mut query {
+        if r.0.len() != length.0 {
+            r.resize(length.0, last_frame.0);
+        }
     }
 }
 
+/// Resizes the [`Rollback<T>`] [`Resource`] to [`HistoryLength`], used in the [`RollbackResize`](crate::schedule_plugin::RollbackResize) [`Schedule`]
+#[cfg(feature = "growable")]
+pub fn resize_rollback_resource<T: Send + Sync + 'static, const LEN: usize>(
+    length: Res<HistoryLength>,
+    last_frame: Res<LastFrame>,
+    rollback: Option<ResMut<Rollback<T, LEN>>>,
+) {
+    if let Some(mut rollback) = rollback {
+        if rollback.0.len() != length.0 {
+            rollback.resize(length.0, last_frame.0);
+        }
+    }
+}
 
 //TODO: default systems that will take SnapshotUpdateEvent<T> that will simplify the usage
 
@@ -0,0 +1,38 @@
+use std::hash::Hasher;
+
+use bevy::prelude::*;
+use bevy_gravirollback::prelude::*;
+
+#[derive(Reflect)]
+struct Sizes {
+    len: usize,
+    offset: isize,
+}
+
+#[derive(Reflect)]
+struct Fixed {
+    len: u64,
+    offset: i64,
+}
+
+fn fnv(bytes: &[u8]) -> u64 {
+    let mut hasher = ChecksumHasher::default();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+#[test]
+fn checksum_hasher_is_fnv1a() {
+    assert_eq!(fnv(b""), 0xcbf29ce484222325);
+    assert_eq!(fnv(b"a"), 0xaf63dc4c8601ec8c);
+    assert_eq!(fnv(b"foobar"), 0x85944171f73967e8);
+}
+
+#[test]
+fn pointer_sized_integers_are_hashed_as_64_bit() {
+    let mut sizes = ChecksumHasher::default();
+    reflect_checksum(&Sizes { len: 3, offset: -1 }, &mut sizes);
+    let mut fixed = ChecksumHasher::default();
+    reflect_checksum(&Fixed { len: 3, offset: -1 }, &mut fixed);
+    assert_eq!(sizes.finish(), fixed.finish());
+}
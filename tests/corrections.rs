@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use bevy_gravirollback::prelude::*;
+
+const LEN: usize = 16;
+
+#[derive(Component, Clone, PartialEq, Debug)]
+struct Pos(i64);
+
+fn step(mut query: Query<&mut Pos>) {
+    for mut pos in &mut query {
+        pos.0 += 1;
+    }
+}
+
+fn pos(app: &mut App) -> i64 {
+    app.world_mut().query::<&Pos>().single(app.world()).0
+}
+
+#[test]
+fn correction_during_resimulation() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default(), RollbackCorrectionPlugin::<Pos, LEN>::default()))
+        .add_systems(RollbackUpdate, step.in_set(RollbackUpdateSet::Update));
+    RollbackSystemConfigurator::<LEN>::default().add::<(Pos,)>().apply(&mut app);
+    app.world_mut().spawn((RollbackID(1), Pos(0), Rollback::<Pos, LEN>::default()));
+    for frame in 1..=6 {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+    assert_eq!(pos(&mut app), 6);
+
+    //resimulate from frame 2, one frame per update
+    app.world_mut().resource_mut::<RollbackUpdateConfig>().max_update_loops = 1;
+    app.world_mut().resource_mut::<Rollback<Modified, LEN>>().set(2, Modified(true));
+    app.update();
+    assert_eq!(app.world().resource::<Frame>().0, 3);
+
+    //frame 5 is corrected before the resimulation reaches it
+    app.world_mut().send_event(CorrectComponent { frame: 5, id: RollbackID(1), value: Pos(100) });
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(app.world().resource::<Frame>().0, 6);
+    let rollback = app.world_mut().query::<&Rollback<Pos, LEN>>().single(app.world()).clone();
+    assert_eq!(rollback.get(5), Some(&Pos(100)));
+    assert_eq!(pos(&mut app), 101);
+}
+
+#[test]
+fn correction_newer_than_restored_frame() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default(), RollbackCorrectionPlugin::<Pos, LEN>::default()))
+        .add_systems(RollbackUpdate, step.in_set(RollbackUpdateSet::Update));
+    RollbackSystemConfigurator::<LEN>::default().add::<(Pos,)>().apply(&mut app);
+    app.world_mut().spawn((RollbackID(1), Pos(0), Rollback::<Pos, LEN>::default()));
+    for frame in 1..=6 {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+
+    //the rollback restores frame 2 and resimulates over the corrected frame 4
+    app.world_mut().resource_mut::<Rollback<Modified, LEN>>().set(2, Modified(true));
+    app.world_mut().send_event(CorrectComponent { frame: 4, id: RollbackID(1), value: Pos(100) });
+    app.update();
+    assert_eq!(app.world().resource::<Frame>().0, 6);
+    assert_eq!(pos(&mut app), 102);
+}
+
+#[test]
+fn correction_survives_later_rollback() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default(), RollbackCorrectionPlugin::<Pos, LEN>::default()))
+        .add_systems(RollbackUpdate, step.in_set(RollbackUpdateSet::Update));
+    RollbackSystemConfigurator::<LEN>::default().add::<(Pos,)>().apply(&mut app);
+    app.world_mut().spawn((RollbackID(1), Pos(0), Rollback::<Pos, LEN>::default()));
+    for frame in 1..=6 {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+
+    app.world_mut().send_event(CorrectComponent { frame: 5, id: RollbackID(1), value: Pos(100) });
+    app.update();
+    assert_eq!(pos(&mut app), 101);
+
+    //a later rollback from an older frame resimulates over frame 5 again
+    app.world_mut().resource_mut::<Rollback<Modified, LEN>>().set(2, Modified(true));
+    app.update();
+    assert_eq!(app.world().resource::<Frame>().0, 6);
+    let rollback = app.world_mut().query::<&Rollback<Pos, LEN>>().single(app.world()).clone();
+    assert_eq!(rollback.get(5), Some(&Pos(100)));
+    assert_eq!(pos(&mut app), 101);
+}
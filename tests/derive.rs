@@ -0,0 +1,72 @@
+#![cfg(feature = "derive")]
+
+use bevy::prelude::*;
+use bevy_gravirollback::prelude::*;
+
+const LEN: usize = 16;
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Pos(i64);
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Vel(i64);
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Speed(i64);
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Hits(i64);
+
+#[derive(RollbackCapable, Default)]
+struct Body {
+    pos: Pos,
+    vel: Vel,
+    #[rollback(derived = Body::speed)]
+    speed: Speed,
+    #[rollback(save_only)]
+    hits: Hits,
+}
+
+impl Body {
+    fn speed(&self) -> Speed {
+        Speed(self.vel.0.abs())
+    }
+}
+
+fn step(mut query: Query<(&mut Pos, &mut Vel, &mut Speed, &mut Hits)>) {
+    for (mut pos, mut vel, mut speed, mut hits) in &mut query {
+        pos.0 += vel.0;
+        vel.0 -= 1;
+        speed.0 = 100;
+        hits.0 += 1;
+    }
+}
+
+#[test]
+fn derived_rollback_capable() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default()))
+        .add_systems(RollbackUpdate, step.in_set(RollbackUpdateSet::Update));
+    RollbackSystemConfigurator::<LEN>::default().add::<(Body,)>().apply(&mut app);
+    let entity = app.world_mut().spawn((RollbackID(1), Pos(0), Vel(3), Speed(0), Hits(0), Rollback::<Body, LEN>::default())).id();
+    for frame in 1..=5 {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+    let body = app.world().get::<Rollback<Body, LEN>>(entity).unwrap().get(2).unwrap();
+    assert_eq!((&body.pos, &body.vel, &body.hits), (&Pos(5), &Vel(1), &Hits(2)));
+    //derived fields are not saved
+    assert_eq!(body.speed, Speed(0));
+
+    //restore frame 2 and stay there
+    app.world_mut().resource_mut::<Rollback<Modified, LEN>>().set(2, Modified(true));
+    app.world_mut().resource_mut::<WantedFrame>().0 = 2;
+    app.update();
+    assert_eq!(app.world().get::<Pos>(entity), Some(&Pos(5)));
+    assert_eq!(app.world().get::<Vel>(entity), Some(&Vel(1)));
+    //recomputed from the restored snapshot
+    assert_eq!(app.world().get::<Speed>(entity), Some(&Speed(1)));
+    //save only fields are not restored
+    assert_eq!(app.world().get::<Hits>(entity), Some(&Hits(5)));
+}
@@ -0,0 +1,66 @@
+use bevy::prelude::*;
+use bevy_gravirollback::prelude::*;
+
+const LEN: usize = 8;
+
+#[derive(Component, Clone, PartialEq, Debug)]
+struct Pos(i64);
+
+#[derive(Component)]
+struct Moving;
+
+#[derive(Resource, Default)]
+struct RestoredStatic(usize);
+
+fn step(mut query: Query<&mut Pos, With<Moving>>) {
+    for mut pos in &mut query {
+        pos.0 += 1;
+    }
+}
+
+//counts the entities which do not move but were written by the last restore
+fn count_restored(query: Query<Ref<Pos>, Without<Moving>>, changes: RestoreChanges, mut restored: ResMut<RestoredStatic>) {
+    restored.0 = query.iter().filter(|x| changes.changed_by_restore(x)).count();
+}
+
+fn pos(app: &mut App, id: u64) -> i64 {
+    app.world_mut().query::<(&RollbackID, &Pos)>().iter(app.world()).find(|x| x.0.0 == id).unwrap().1.0
+}
+
+#[test]
+fn restore_dirty_skips_unchanged_entities() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default(), RollbackCorrectionPlugin::<Pos, LEN>::default()))
+        .init_resource::<RestoredStatic>()
+        .add_systems(RollbackUpdate, step.in_set(RollbackUpdateSet::Update))
+        .add_systems(Last, count_restored);
+    RollbackSystemConfigurator::<LEN>::default().add_dirty::<Pos>().apply(&mut app);
+    app.world_mut().spawn((RollbackID(0), Pos(0), Moving, Rollback::<Pos, LEN>::default()));
+    for id in 1..10 {
+        app.world_mut().spawn((RollbackID(id), Pos(id as i64), Rollback::<Pos, LEN>::default()));
+    }
+    for frame in 1..=5 {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+
+    //only the moving entity changed since frame 2
+    app.world_mut().resource_mut::<Rollback<Modified, LEN>>().set(2, Modified(true));
+    app.update();
+    assert_eq!(app.world().resource::<RestoredStatic>().0, 0);
+    assert_eq!(pos(&mut app, 0), 5);
+
+    //the corrected entity differs from its snapshot of frame 3, so it is restored
+    app.world_mut().send_event(CorrectComponent { frame: 3, id: RollbackID(7), value: Pos(-1) });
+    app.update();
+    assert_eq!(app.world().resource::<RestoredStatic>().0, 1);
+    assert_eq!(pos(&mut app, 7), -1);
+
+    //it did not change since frame 4, so it is skipped again
+    app.world_mut().resource_mut::<Rollback<Modified, LEN>>().set(4, Modified(true));
+    app.update();
+    assert_eq!(app.world().resource::<RestoredStatic>().0, 0);
+    assert_eq!(pos(&mut app, 7), -1);
+    assert_eq!(pos(&mut app, 0), 5);
+}
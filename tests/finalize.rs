@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use bevy_gravirollback::prelude::*;
+
+const LEN: usize = 16;
+
+#[derive(Resource, Default)]
+struct Finalized(Vec<u64>);
+
+fn record(mut events: EventReader<FrameFinalized>, mut finalized: ResMut<Finalized>) {
+    finalized.0.extend(events.read().map(|x| x.0));
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default(), RollbackInputPlugin::<i64, LEN>::default()))
+        .init_resource::<Finalized>()
+        .add_systems(Last, record);
+    app
+}
+
+fn insert_inputs(app: &mut App, frames: std::ops::Range<u64>) {
+    for frame in frames {
+        app.world_mut().send_event(InsertInput { frame, player: PlayerHandle(0), data: 1i64 });
+    }
+}
+
+#[test]
+fn frames_with_confirmed_inputs_are_finalized_in_order() {
+    let mut app = app();
+    insert_inputs(&mut app, 0..3);
+    for frame in 1..=5 {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+    assert_eq!(app.world().resource::<ConfirmedFrame>().0, Some(2));
+    assert_eq!(app.world().resource::<FinalizedFrame>().0, Some(2));
+    assert_eq!(app.world().resource::<Finalized>().0, vec![0, 1, 2]);
+
+    insert_inputs(&mut app, 3..4);
+    app.update();
+    assert_eq!(app.world().resource::<Finalized>().0, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn nothing_is_finalized_without_confirmed_inputs() {
+    let mut app = app();
+    for frame in 1..=5 {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+    assert_eq!(app.world().resource::<ConfirmedFrame>().0, None);
+    assert!(app.world().resource::<Finalized>().0.is_empty());
+}
+
+#[test]
+fn unfinished_resimulation_is_not_finalized() {
+    let mut app = app();
+    for frame in 1..=6 {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+
+    //the late inputs differ from the predictions, the resimulation from frame 0 runs one frame per update
+    app.world_mut().resource_mut::<RollbackUpdateConfig>().max_update_loops = 1;
+    insert_inputs(&mut app, 0..5);
+    app.update();
+    assert_eq!(app.world().resource::<Frame>().0, 1);
+    assert_eq!(app.world().resource::<Finalized>().0, vec![0]);
+
+    app.update();
+    app.update();
+    assert_eq!(app.world().resource::<Frame>().0, 3);
+    assert_eq!(app.world().resource::<Finalized>().0, vec![0, 1, 2]);
+}
@@ -0,0 +1,40 @@
+use bevy::prelude::*;
+use bevy_gravirollback::prelude::*;
+
+const LEN: usize = 8;
+
+#[derive(Component, Clone, PartialEq, Debug)]
+struct Pos(i64);
+
+#[derive(Component, Clone, PartialEq, Debug)]
+struct Vel(i64);
+
+//only a part of the group is inserted, so the group is saved as missing
+fn insert_pos(frame: Res<Frame>, query: Query<Entity, With<RollbackID>>, mut commands: Commands) {
+    if frame.0 == 3 {
+        for e in &query {
+            commands.entity(e).insert(Pos(1));
+        }
+    }
+}
+
+#[test]
+fn restore_removes_partial_group() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default()))
+        .add_systems(RollbackUpdate, insert_pos.in_set(RollbackUpdateSet::Update));
+    RollbackSystemConfigurator::<LEN>::default().add_option::<(Group<(Pos, Vel)>,)>().apply(&mut app);
+    let entity = app.world_mut().spawn((RollbackID(1), Rollback::<Option<Group<(Pos, Vel)>>, LEN>::default())).id();
+    for frame in 1..=5 {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+    assert_eq!(app.world().get::<Pos>(entity), Some(&Pos(1)));
+
+    //restore frame 2 and stay there
+    app.world_mut().resource_mut::<Rollback<Modified, LEN>>().set(2, Modified(true));
+    app.world_mut().resource_mut::<WantedFrame>().0 = 2;
+    app.update();
+    assert_eq!(app.world().get::<Pos>(entity), None);
+}
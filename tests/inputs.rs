@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use bevy::ecs::system::RunSystemOnce;
+use bevy_gravirollback::prelude::*;
+
+const LEN: usize = 16;
+
+#[derive(Resource, Reflect, Clone, Default, PartialEq, Debug)]
+struct Sum(i64);
+
+fn step(mut sum: ResMut<Sum>, inputs: Res<FrameInputs<i64>>) {
+    for slot in inputs.iter() {
+        sum.0 += slot.input;
+    }
+}
+
+fn app(players: usize) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default(), RollbackInputPlugin::<i64, LEN> { players, ..default() }))
+        .init_resource::<Sum>()
+        .add_systems(RollbackUpdate, step.in_set(RollbackUpdateSet::Update));
+    RollbackSystemConfigurator::<LEN>::default().add_resource::<Sum>().apply(&mut app);
+    app
+}
+
+fn run_to(app: &mut App, frames: std::ops::RangeInclusive<u64>) {
+    for frame in frames {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+}
+
+fn confirm(app: &mut App, frame: u64, player: usize, input: i64) {
+    app.world_mut().run_system_once(move |mut writer: InputWriter<i64, LEN>| {
+        writer.confirm(frame, PlayerHandle(player), input).unwrap();
+    }).unwrap();
+}
+
+fn modified_frames(app: &App, frames: std::ops::RangeInclusive<u64>) -> Vec<u64> {
+    let modified = app.world().resource::<Rollback<Modified, LEN>>();
+    frames.filter(|x| modified.get(*x).is_some_and(|x| x.0)).collect()
+}
+
+#[test]
+fn late_input_replaces_prediction() {
+    let mut app = app(2);
+    confirm(&mut app, 0, 0, 1);
+    confirm(&mut app, 0, 1, 1);
+    run_to(&mut app, 1..=5);
+    //the confirmed inputs of frame 0 are repeated by the prediction
+    assert_eq!(app.world().resource::<Sum>().0, 10);
+    let inputs = app.world().resource::<RollbackInputs<i64, LEN>>();
+    assert_eq!(inputs.status(PlayerHandle(1), 2), Some(InputStatus::Predicted));
+    assert_eq!(inputs.confirmed_frame(), Some(0));
+
+    confirm(&mut app, 2, 1, 3);
+    assert_eq!(app.world().resource::<RollbackInputs<i64, LEN>>().status(PlayerHandle(1), 2), Some(InputStatus::Confirmed));
+    //frames 3 and 4 were predicted from the changed input
+    assert_eq!(modified_frames(&app, 0..=5), vec![2, 3, 4]);
+
+    app.update();
+    //player 0 keeps 1 in all 5 frames, player 1 has 3 from frame 2
+    assert_eq!(app.world().resource::<Sum>().0, 5 + (1 + 1 + 3 + 3 + 3));
+    assert_eq!(app.world().resource::<RollbackInputs<i64, LEN>>().status(PlayerHandle(1), 3), Some(InputStatus::Predicted));
+}
+
+#[test]
+fn input_equal_to_prediction_does_not_modify() {
+    let mut app = app(1);
+    run_to(&mut app, 1..=5);
+    confirm(&mut app, 2, 0, 0);
+    assert!(modified_frames(&app, 0..=5).is_empty());
+}
+
+#[test]
+fn changed_input_stops_at_confirmed_input() {
+    let mut app = app(1);
+    run_to(&mut app, 1..=6);
+    confirm(&mut app, 4, 0, 0);
+    confirm(&mut app, 2, 0, 5);
+    assert_eq!(modified_frames(&app, 0..=6), vec![2, 3]);
+}
+
+#[test]
+fn future_input_is_used_when_simulated() {
+    let mut app = app(1);
+    run_to(&mut app, 1..=2);
+    confirm(&mut app, 4, 0, 7);
+    let inputs = app.world().resource::<RollbackInputs<i64, LEN>>();
+    assert_eq!(inputs.status(PlayerHandle(0), 4), Some(InputStatus::Confirmed));
+    assert!(modified_frames(&app, 0..=2).is_empty());
+
+    run_to(&mut app, 3..=5);
+    assert_eq!(app.world().resource::<Sum>().0, 7);
+}
+
+#[test]
+fn input_older_than_history_is_rejected() {
+    let mut app = app(1);
+    run_to(&mut app, 1..=20);
+    let mut inputs = app.world_mut().resource_mut::<RollbackInputs<i64, LEN>>();
+    assert_eq!(inputs.confirm(2, PlayerHandle(0), 1, 20), Err(InputError::TooOld { frame: 2, oldest_frame: 5 }));
+    assert_eq!(inputs.confirm(5, PlayerHandle(3), 1, 20), Err(InputError::UnknownPlayer(PlayerHandle(3))));
+}
@@ -0,0 +1,47 @@
+use bevy::prelude::*;
+use bevy_gravirollback::prelude::*;
+
+const LEN: usize = 8;
+
+#[derive(Component, Clone, PartialEq, Debug)]
+struct Shield(u32);
+
+#[derive(Resource, Default)]
+struct Seen(Vec<(u64, bool, bool)>);
+
+fn remove_shield(frame: Res<Frame>, query: Query<Entity, With<Shield>>, mut commands: Commands) {
+    if frame.0 == 3 {
+        for e in &query {
+            commands.entity(e).remove::<Shield>();
+        }
+    }
+}
+
+fn record(frame: Res<Frame>, query: Query<Ref<Shield>>, changes: RestoreChanges, mut seen: ResMut<Seen>) {
+    for shield in &query {
+        seen.0.push((frame.0, changes.changed_by_restore(&shield), changes.changed_by_simulation(&shield)));
+    }
+}
+
+#[test]
+fn component_inserted_by_restore_command() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default()))
+        .init_resource::<Seen>()
+        .add_systems(RollbackUpdate, (record, remove_shield).chain().in_set(RollbackUpdateSet::Update));
+    RollbackSystemConfigurator::<LEN>::default().add_option::<(Shield,)>().apply(&mut app);
+    app.world_mut().spawn((RollbackID(1), Shield(1), Rollback::<Option<Shield>, LEN>::default()));
+    for frame in 1..=5 {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+    assert_eq!(app.world_mut().query::<&Shield>().iter(app.world()).count(), 0);
+    app.world_mut().resource_mut::<Seen>().0.clear();
+
+    //the Shield was removed in frame 4, the restore of frame 2 inserts it again by a command
+    app.world_mut().resource_mut::<Rollback<Modified, LEN>>().set(2, Modified(true));
+    app.update();
+    let seen = &app.world().resource::<Seen>().0;
+    assert_eq!(seen.first(), Some(&(2, true, false)), "{seen:?}");
+}
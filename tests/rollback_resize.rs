@@ -0,0 +1,75 @@
+#![cfg(feature = "growable")]
+
+use bevy::prelude::*;
+use bevy_gravirollback::prelude::*;
+
+const LEN: usize = 4;
+
+#[derive(Component, Clone, PartialEq, Debug)]
+struct Pos(i64);
+
+fn filled(frames: std::ops::RangeInclusive<u64>) -> Rollback<u64, LEN> {
+    let mut rollback = Rollback::<u64, LEN>::default();
+    for frame in frames {
+        rollback.set(frame, frame * 10);
+    }
+    rollback
+}
+
+#[test]
+fn grow_keeps_all_frames() {
+    let mut rollback = filled(3..=6);
+    rollback.resize(8, 6);
+    assert_eq!(rollback.len(), 8);
+    for frame in 3..=6 {
+        assert_eq!(rollback.get(frame), Some(&(frame * 10)));
+    }
+    assert_eq!(rollback.get(2), None);
+    assert_eq!(rollback.iter_saved().count(), 4);
+}
+
+#[test]
+fn shrink_keeps_newest_frames() {
+    let mut rollback = filled(3..=6);
+    rollback.resize(2, 6);
+    assert_eq!(rollback.len(), 2);
+    assert_eq!(rollback.get(4), None);
+    assert_eq!(rollback.get(5), Some(&50));
+    assert_eq!(rollback.get(6), Some(&60));
+}
+
+#[test]
+fn resize_to_same_length_changes_nothing() {
+    let mut rollback = filled(3..=6);
+    rollback.resize(LEN, 2);
+    for frame in 3..=6 {
+        assert_eq!(rollback.get(frame), Some(&(frame * 10)));
+    }
+}
+
+#[test]
+fn added_component_uses_history_length() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(HistoryLength(10))
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default()));
+    let entity = app.world_mut().spawn(make_rollback::<Pos, LEN>(Pos(0))).id();
+    assert_eq!(app.world().get::<Rollback<Pos, LEN>>(entity).unwrap().len(), 10);
+
+    let entity = app.world_mut().spawn(Rollback::<Pos, LEN>::new(7, Pos(1))).id();
+    let rollback = app.world().get::<Rollback<Pos, LEN>>(entity).unwrap();
+    assert_eq!(rollback.len(), 10);
+    assert_eq!(rollback.get(7), Some(&Pos(1)));
+}
+
+#[test]
+fn zero_history_length_is_rejected() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default()));
+    app.world_mut().resource_mut::<HistoryLength>().0 = 0;
+    app.world_mut().resource_mut::<WantedFrame>().0 = 1;
+    app.update();
+    assert_eq!(app.world().resource::<HistoryLength>().0, LEN);
+    assert_eq!(app.world().resource::<Frame>().0, 1);
+}
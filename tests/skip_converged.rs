@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+use bevy_gravirollback::prelude::*;
+
+const LEN: usize = 16;
+
+#[derive(Component, Reflect, Clone, PartialEq, Debug)]
+struct Pos(i64);
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct Input(u8);
+
+#[derive(Resource, Default)]
+struct Steps(u32);
+
+//only inputs over 100 move the entity faster
+fn step(mut query: Query<&mut Pos>, inputs: Res<FrameInputs<Input>>, mut steps: ResMut<Steps>) {
+    steps.0 += 1;
+    let fast = inputs.get(PlayerHandle(0)).is_some_and(|x| x.input.0 > 100);
+    for mut pos in &mut query {
+        pos.0 += if fast { 10 } else { 1 };
+    }
+}
+
+fn resimulate_with_late_input(input: u8) -> (u32, i64) {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins((RollbackPlugin::<LEN>, RollbackSchedulePlugin::<LEN>::default(), ChecksumPlugin::<LEN>))
+        .add_plugins(RollbackInputPlugin::<Input, LEN> { predict: |_| Input::default(), ..default() })
+        .init_resource::<Steps>()
+        .insert_resource(RollbackUpdateConfig { skip_converged: true, ..default() })
+        .add_systems(RollbackUpdate, step.in_set(RollbackUpdateSet::Update));
+    RollbackSystemConfigurator::<LEN>::default().add::<(Pos,)>().add_checksum::<(Pos,)>().apply(&mut app);
+    app.world_mut().spawn((RollbackID(1), Pos(0), Rollback::<Pos, LEN>::default()));
+    for frame in 1..=5 {
+        app.world_mut().resource_mut::<WantedFrame>().0 = frame;
+        app.update();
+    }
+
+    app.world_mut().resource_mut::<Steps>().0 = 0;
+    app.world_mut().send_event(InsertInput { frame: 2, player: PlayerHandle(0), data: Input(input) });
+    app.world_mut().resource_mut::<WantedFrame>().0 = 6;
+    app.update();
+    assert_eq!(app.world().resource::<Frame>().0, 6);
+    let pos = app.world_mut().query::<&Pos>().single(app.world()).0;
+    (app.world().resource::<Steps>().0, pos)
+}
+
+#[test]
+fn late_input_without_effect_converges() {
+    //frame 2 is resimulated, frames 3 and 4 are skipped and frame 5 is simulated
+    assert_eq!(resimulate_with_late_input(5), (2, 6));
+}
+
+#[test]
+fn late_input_with_effect_resimulates() {
+    assert_eq!(resimulate_with_late_input(200), (4, 15));
+}